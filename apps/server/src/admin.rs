@@ -0,0 +1,325 @@
+use std::sync::Arc;
+
+use axum::{
+    Json, Router,
+    extract::{Path, Query, State},
+    response::{IntoResponse, Response},
+    routing::{delete, get, post},
+};
+use http::{HeaderMap, StatusCode, header::AUTHORIZATION};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    AppState, RoomParams, close_room, error_response,
+    game::{GameState, Role, Room},
+    player::{Player, PlayerId},
+    rate_limit::LimitStats,
+    tokens::tokens_match,
+    ws_msg::{ErrorCode, WsMsg},
+};
+
+/// Host-only REST controls, for scripts and overlays that don't hold a WebSocket open.
+pub fn routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/{code}", delete(delete_room))
+        .route("/{code}/admin/status", get(status))
+        .route("/{code}/admin/players", get(players))
+        .route("/{code}/admin/scores", get(score_log))
+        .route("/{code}/admin/heartbeat", post(heartbeat))
+        .route("/{code}/admin/end", post(end_game))
+}
+
+#[derive(Deserialize)]
+pub struct AdminQuery {
+    token: Option<String>, // for clients that can't set headers, e.g. overlays
+}
+
+/// The host token from `Authorization: Bearer <token>`, or the `token` query parameter.
+pub fn host_token<'a>(headers: &'a HeaderMap, query: &'a AdminQuery) -> Option<&'a str> {
+    headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .or(query.token.as_deref())
+}
+
+/// Checks the caller holds the room's host token, returns why not otherwise.
+pub fn authorize(room: &Room, headers: &HeaderMap, query: &AdminQuery) -> Result<(), &'static str> {
+    match host_token(headers, query) {
+        Some(token) if tokens_match(token, &room.host_token) => Ok(()),
+        Some(_) => Err("Invalid host token"),
+        None => Err("Missing host token"),
+    }
+}
+
+fn unauthorized(reason: &str) -> Response {
+    error_response(StatusCode::UNAUTHORIZED, reason.to_string())
+}
+
+fn room_not_found(code: &str) -> Response {
+    error_response(StatusCode::NOT_FOUND, format!("Room {code} does not exist"))
+}
+
+#[derive(Serialize)]
+struct RoomStatus {
+    #[serde(rename = "roomCode")]
+    room_code: String,
+    state: GameState,
+    round: usize,
+    #[serde(rename = "currentQuestion")]
+    current_question: Option<(usize, usize)>,
+    #[serde(rename = "currentBuzzer")]
+    current_buzzer: Option<PlayerId>,
+    #[serde(rename = "hostConnected")]
+    host_connected: bool,
+    displays: usize,
+    players: Vec<Player>,
+    limits: LimitStats, // rate limit hits across every connection to the room
+}
+
+impl RoomStatus {
+    fn of(room: &Room) -> Self {
+        Self {
+            room_code: room.code.clone(),
+            state: room.state.clone(),
+            round: room.current_round,
+            current_question: room.current_question,
+            current_buzzer: room.current_buzzer,
+            host_connected: room.host.as_ref().is_some_and(|h| !h.sender.is_closed()),
+            displays: room.displays.len(),
+            players: room.players.iter().map(|e| e.player.clone()).collect(),
+            limits: room.limit_counters.stats(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct HeartbeatResult {
+    requested: usize,
+    failed: u32, // failed immediately, e.g. the player's channel is gone
+}
+
+async fn status(
+    State(state): State<Arc<AppState>>,
+    Path(RoomParams { code }): Path<RoomParams>,
+    Query(query): Query<AdminQuery>,
+    headers: HeaderMap,
+) -> Response {
+    let room_map = state.room_map.lock().await;
+    let Some(room) = room_map.get(&code) else {
+        return room_not_found(&code);
+    };
+    if let Err(reason) = authorize(room, &headers, &query) {
+        return unauthorized(reason);
+    }
+    Json(RoomStatus::of(room)).into_response()
+}
+
+async fn players(
+    State(state): State<Arc<AppState>>,
+    Path(RoomParams { code }): Path<RoomParams>,
+    Query(query): Query<AdminQuery>,
+    headers: HeaderMap,
+) -> Response {
+    let room_map = state.room_map.lock().await;
+    let Some(room) = room_map.get(&code) else {
+        return room_not_found(&code);
+    };
+    if let Err(reason) = authorize(room, &headers, &query) {
+        return unauthorized(reason);
+    }
+    let players: Vec<Player> = room.players.iter().map(|e| e.player.clone()).collect();
+    Json(players).into_response()
+}
+
+/// Every manual score change made by the host, oldest first.
+async fn score_log(
+    State(state): State<Arc<AppState>>,
+    Path(RoomParams { code }): Path<RoomParams>,
+    Query(query): Query<AdminQuery>,
+    headers: HeaderMap,
+) -> Response {
+    let room_map = state.room_map.lock().await;
+    let Some(room) = room_map.get(&code) else {
+        return room_not_found(&code);
+    };
+    if let Err(reason) = authorize(room, &headers, &query) {
+        return unauthorized(reason);
+    }
+    Json(&room.score_log).into_response()
+}
+
+async fn heartbeat(
+    State(state): State<Arc<AppState>>,
+    Path(RoomParams { code }): Path<RoomParams>,
+    Query(query): Query<AdminQuery>,
+    headers: HeaderMap,
+) -> Response {
+    let heartbeats = {
+        let mut room_map = state.room_map.lock().await;
+        let Some(room) = room_map.get_mut(&code) else {
+            return room_not_found(&code);
+        };
+        if let Err(reason) = authorize(room, &headers, &query) {
+            return unauthorized(reason);
+        }
+        room.heartbeat_all()
+    };
+    let requested = heartbeats.len();
+    let failed = heartbeats.send().await;
+    Json(HeartbeatResult { requested, failed }).into_response()
+}
+
+/// Same as the host sending EndGame.
+async fn end_game(
+    State(state): State<Arc<AppState>>,
+    Path(RoomParams { code }): Path<RoomParams>,
+    Query(query): Query<AdminQuery>,
+    headers: HeaderMap,
+) -> Response {
+    let mut room_map = state.room_map.lock().await;
+    let Some(room) = room_map.get_mut(&code) else {
+        return room_not_found(&code);
+    };
+    if let Err(reason) = authorize(room, &headers, &query) {
+        return unauthorized(reason);
+    }
+    if let Err(e) = room.update(&WsMsg::EndGame {}, Role::Host).await {
+        return error_response(StatusCode::INTERNAL_SERVER_ERROR, format!("{e}"));
+    }
+    Json(RoomStatus::of(room)).into_response()
+}
+
+/// Closes every connection and forgets the room. Its ticker stops on its own.
+async fn delete_room(
+    State(state): State<Arc<AppState>>,
+    Path(RoomParams { code }): Path<RoomParams>,
+    Query(query): Query<AdminQuery>,
+    headers: HeaderMap,
+) -> Response {
+    let mut room_map = state.room_map.lock().await;
+    let Some(room) = room_map.get(&code) else {
+        return room_not_found(&code);
+    };
+    if let Err(reason) = authorize(room, &headers, &query) {
+        return unauthorized(reason);
+    }
+    let Some(room) = room_map.remove(&code) else {
+        return room_not_found(&code);
+    };
+    close_room(&room, ErrorCode::RoomClosed).await;
+    StatusCode::NO_CONTENT.into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use http::HeaderValue;
+
+    use super::*;
+    use crate::names::Blocklist;
+
+    const CODE: &str = "ABCDEF";
+
+    fn test_state() -> Arc<AppState> {
+        let state = Arc::new(AppState::new(Blocklist::default(), None));
+        let room = Room::new(
+            CODE.to_string(),
+            "host-token".to_string(),
+            "display".to_string(),
+        );
+        state
+            .room_map
+            .try_lock()
+            .expect("unlocked")
+            .insert(CODE.to_string(), room);
+        state
+    }
+
+    fn bearer(token: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        let value = HeaderValue::from_str(&format!("Bearer {token}")).expect("header value");
+        headers.insert(AUTHORIZATION, value);
+        headers
+    }
+
+    fn query(token: Option<&str>) -> Query<AdminQuery> {
+        Query(AdminQuery {
+            token: token.map(str::to_string),
+        })
+    }
+
+    fn path(code: &str) -> Path<RoomParams> {
+        Path(RoomParams {
+            code: code.to_string(),
+        })
+    }
+
+    #[tokio::test]
+    async fn the_host_token_is_accepted_from_a_header_or_the_query() {
+        let state = test_state();
+        let by_header = status(
+            State(state.clone()),
+            path(CODE),
+            query(None),
+            bearer("host-token"),
+        )
+        .await;
+        assert_eq!(by_header.status(), StatusCode::OK);
+        let by_query = status(
+            State(state),
+            path(CODE),
+            query(Some("host-token")),
+            HeaderMap::new(),
+        )
+        .await;
+        assert_eq!(by_query.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn bad_tokens_and_unknown_rooms_are_refused() {
+        let state = test_state();
+        for (headers, token) in [
+            (HeaderMap::new(), None),
+            (bearer("display"), None),
+            (HeaderMap::new(), Some("host-token-but-longer")),
+        ] {
+            let res = players(State(state.clone()), path(CODE), query(token), headers).await;
+            assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+        }
+        let res = status(
+            State(state),
+            path("ZZZZZZ"),
+            query(None),
+            bearer("host-token"),
+        )
+        .await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn deleting_a_room_needs_the_host_token() {
+        let state = test_state();
+        let res = delete_room(
+            State(state.clone()),
+            path(CODE),
+            query(None),
+            bearer("display"),
+        )
+        .await;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+        assert!(state.room_map.lock().await.contains_key(CODE));
+
+        let res = delete_room(
+            State(state.clone()),
+            path(CODE),
+            query(None),
+            bearer("host-token"),
+        )
+        .await;
+        assert_eq!(res.status(), StatusCode::NO_CONTENT);
+        assert!(!state.room_map.lock().await.contains_key(CODE));
+
+        let res = delete_room(State(state), path(CODE), query(None), bearer("host-token")).await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,77 @@
+use std::net::{IpAddr, SocketAddr};
+
+use http::{HeaderMap, HeaderName};
+
+/// Environment variable naming the header a trusted reverse proxy puts the client's address in,
+/// e.g. "X-Forwarded-For" or "X-Real-IP". Unset means the socket's peer address is used.
+pub const PROXY_HEADER_ENV: &str = "TRUSTED_PROXY_HEADER";
+
+/// The header to take client addresses from, None if it isn't configured or isn't a valid name.
+pub fn proxy_header_from_env() -> Option<HeaderName> {
+    let name = std::env::var(PROXY_HEADER_ENV).ok()?;
+    match HeaderName::try_from(name.trim()) {
+        Ok(header) => Some(header),
+        Err(e) => {
+            println!("ignoring {PROXY_HEADER_ENV}={name:?}: {e}");
+            None
+        }
+    }
+}
+
+/// Where a connection really comes from, for IP bans.
+///
+/// Only the last address in the header is used: that is the one our proxy added, anything
+/// before it came from the client and can be forged. Falls back to the peer address when
+/// the header is missing or unparseable.
+pub fn client_ip(
+    headers: &HeaderMap,
+    peer: SocketAddr,
+    proxy_header: Option<&HeaderName>,
+) -> IpAddr {
+    proxy_header
+        .and_then(|name| headers.get_all(name).iter().next_back())
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.rsplit(',').next())
+        .and_then(|last| last.trim().parse().ok())
+        .unwrap_or_else(|| peer.ip())
+}
+
+#[cfg(test)]
+mod tests {
+    use http::HeaderValue;
+
+    use super::*;
+
+    const PEER: SocketAddr =
+        SocketAddr::new(IpAddr::V4(std::net::Ipv4Addr::new(172, 17, 0, 1)), 4321);
+
+    fn headers(name: &'static str, value: &'static str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(name, HeaderValue::from_static(value));
+        headers
+    }
+
+    #[test]
+    fn the_proxy_header_is_only_read_when_configured() {
+        let forwarded = headers("x-forwarded-for", "203.0.113.7");
+        assert_eq!(client_ip(&forwarded, PEER, None), PEER.ip());
+        let header = HeaderName::from_static("x-forwarded-for");
+        assert_eq!(
+            client_ip(&forwarded, PEER, Some(&header)),
+            IpAddr::from([203, 0, 113, 7])
+        );
+        assert_eq!(client_ip(&HeaderMap::new(), PEER, Some(&header)), PEER.ip());
+    }
+
+    #[test]
+    fn only_the_address_our_proxy_added_counts() {
+        let header = HeaderName::from_static("x-forwarded-for");
+        let spoofed = headers("x-forwarded-for", "10.0.0.1, 203.0.113.7");
+        assert_eq!(
+            client_ip(&spoofed, PEER, Some(&header)),
+            IpAddr::from([203, 0, 113, 7])
+        );
+        let garbage = headers("x-forwarded-for", "203.0.113.7, not-an-ip");
+        assert_eq!(client_ip(&garbage, PEER, Some(&header)), PEER.ip());
+    }
+}
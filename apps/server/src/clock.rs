@@ -0,0 +1,205 @@
+use std::collections::VecDeque;
+
+use serde::{Deserialize, Serialize};
+
+use crate::UnixMs;
+
+/// How many accepted samples the estimator keeps, and how many raw round trips it judges outliers by.
+const WINDOW: usize = 8;
+/// Samples needed before outlier rejection kicks in.
+const MIN_SAMPLES_FOR_FILTER: usize = 3;
+/// A sample is an outlier if its round trip exceeds the median of recent round trips,
+/// rejected ones included, by this factor plus slack. Counting rejected samples lets the
+/// cutoff follow a lasting change in latency instead of rejecting everything after it.
+const OUTLIER_FACTOR: f64 = 2.0;
+const OUTLIER_SLACK_MS: f64 = 20.0;
+
+#[derive(Clone, Copy, Debug)]
+struct ClockSample {
+    offset: f64, // client clock minus server clock
+    rtt: f64,
+}
+
+/// What the server currently believes about a player's connection.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+pub struct ClockStats {
+    #[serde(rename = "offsetMs")]
+    pub offset_ms: f64, // client clock minus server clock
+    #[serde(rename = "rttMs")]
+    pub rtt_ms: f64,
+    #[serde(rename = "jitterMs")]
+    pub jitter_ms: f64,
+    pub confidence: f64, // 0 (nothing known) to 1 (full window of consistent samples)
+    pub samples: usize,
+    pub rejected: u32,
+}
+
+/// NTP-style estimator of a client's clock offset and round-trip time.
+///
+/// Each heartbeat yields the four NTP timestamps: server send (t1), client receive (t2),
+/// client send (t3) and server receive (t4). Offset and round trip are computed without
+/// assuming synchronised clocks; the offset is taken from the lowest-delay sample in the
+/// window, which is the one least distorted by queueing.
+#[derive(Debug, Default)]
+pub struct ClockEstimator {
+    samples: VecDeque<ClockSample>,
+    recent_rtts: VecDeque<f64>, // every sample's round trip, accepted or not
+    rejected: u32,
+}
+
+impl ClockEstimator {
+    /// Adds a heartbeat round trip, returns false if it was dropped as an outlier.
+    pub fn add_sample(&mut self, t1: UnixMs, t2: UnixMs, t3: UnixMs, t4: UnixMs) -> bool {
+        let (t1, t2, t3, t4) = (t1 as f64, t2 as f64, t3 as f64, t4 as f64);
+        let rtt = ((t4 - t1) - (t3 - t2)).max(0.0);
+        let offset = ((t2 - t1) + (t3 - t4)) / 2.0;
+
+        let cutoff = median(self.recent_rtts.iter().copied()) * OUTLIER_FACTOR + OUTLIER_SLACK_MS;
+        let filtering = self.recent_rtts.len() >= MIN_SAMPLES_FOR_FILTER;
+        self.recent_rtts.push_back(rtt);
+        if self.recent_rtts.len() > WINDOW {
+            self.recent_rtts.pop_front();
+        }
+        if filtering && rtt > cutoff {
+            self.rejected += 1;
+            return false;
+        }
+
+        self.samples.push_back(ClockSample { offset, rtt });
+        if self.samples.len() > WINDOW {
+            self.samples.pop_front();
+        }
+        true
+    }
+
+    fn median_rtt(&self) -> f64 {
+        median(self.samples.iter().map(|s| s.rtt))
+    }
+
+    /// The lowest-delay accepted sample, its offset is the one we trust.
+    fn best(&self) -> Option<ClockSample> {
+        self.samples
+            .iter()
+            .min_by(|a, b| a.rtt.total_cmp(&b.rtt))
+            .copied()
+    }
+
+    pub fn stats(&self) -> ClockStats {
+        let Some(best) = self.best() else {
+            return ClockStats {
+                rejected: self.rejected,
+                ..ClockStats::default()
+            };
+        };
+        let rtt_ms = self.median_rtt();
+        let n = self.samples.len() as f64;
+        let jitter_ms = (self
+            .samples
+            .iter()
+            .map(|s| (s.offset - best.offset).powi(2))
+            .sum::<f64>()
+            / n)
+            .sqrt();
+        let fill = n / WINDOW as f64;
+        let stability = 1.0 / (1.0 + jitter_ms / (rtt_ms / 2.0).max(1.0));
+
+        ClockStats {
+            offset_ms: best.offset,
+            rtt_ms,
+            jitter_ms,
+            confidence: fill * stability,
+            samples: self.samples.len(),
+            rejected: self.rejected,
+        }
+    }
+
+    /// Estimated one-way latency in whole milliseconds, 0 until a sample arrives.
+    pub fn one_way_latency(&self) -> u32 {
+        (self.median_rtt() / 2.0).round() as u32
+    }
+
+    /// When a buzz received at `t_recv` (our clock) was pressed, on our clock.
+    ///
+    /// With the client's own press time this is `t_pressed` shifted by the measured offset,
+    /// which doesn't assume the path is symmetric. It is clamped to the last round trip so a
+    /// client can't claim to have pressed earlier than its connection allows. Without a press
+    /// time, or before any sample arrives, half the round trip is used.
+    pub fn press_time(&self, t_pressed: Option<UnixMs>, t_recv: UnixMs) -> UnixMs {
+        let recv = t_recv as f64;
+        let rtt = self.median_rtt();
+        let at = match (t_pressed, self.best()) {
+            (Some(t_pressed), Some(best)) => {
+                (t_pressed as f64 - best.offset).clamp(recv - rtt, recv)
+            }
+            _ => recv - rtt / 2.0,
+        };
+        at.round().max(0.0) as UnixMs
+    }
+}
+
+fn median(values: impl Iterator<Item = f64>) -> f64 {
+    let mut values: Vec<f64> = values.collect();
+    if values.is_empty() {
+        return 0.0;
+    }
+    values.sort_by(f64::total_cmp);
+    let mid = values.len() / 2;
+    if values.len().is_multiple_of(2) {
+        (values[mid - 1] + values[mid]) / 2.0
+    } else {
+        values[mid]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A round trip on a client whose clock runs `offset` ms ahead, `up` ms there and `down` back.
+    fn sample(
+        clock: &mut ClockEstimator,
+        t1: UnixMs,
+        offset: UnixMs,
+        up: UnixMs,
+        down: UnixMs,
+    ) -> bool {
+        let t2 = t1 + up + offset;
+        clock.add_sample(t1, t2, t2, t1 + up + down)
+    }
+
+    #[test]
+    fn a_lasting_latency_change_is_accepted_eventually() {
+        let mut clock = ClockEstimator::default();
+        for i in 0..WINDOW as UnixMs {
+            assert!(sample(&mut clock, i * 1_000, 0, 10, 10));
+        }
+        assert!(
+            !sample(&mut clock, 20_000, 0, 100, 100),
+            "a lone spike is an outlier"
+        );
+        let accepted = (0..WINDOW as UnixMs)
+            .filter(|i| sample(&mut clock, 30_000 + i * 1_000, 0, 100, 100))
+            .count();
+        assert!(accepted > 0, "the new round trip is never accepted");
+        assert!(clock.stats().rtt_ms > 100.0);
+    }
+
+    #[test]
+    fn press_times_use_the_offset_but_stay_within_a_round_trip() {
+        let mut clock = ClockEstimator::default();
+        assert_eq!(clock.press_time(Some(1), 10_000), 10_000);
+        // Client clock 5 s ahead, 10 ms there and 30 ms back
+        for i in 0..4 {
+            sample(&mut clock, i * 1_000, 5_000, 10, 30);
+        }
+        assert_eq!(clock.press_time(None, 10_000), 9_980);
+        // The measured offset is 4990, the asymmetric path hides 10 ms of it
+        assert_eq!(clock.press_time(Some(14_990), 10_000), 10_000);
+        assert_eq!(clock.press_time(Some(14_970), 10_000), 9_980);
+        assert_eq!(
+            clock.press_time(Some(0), 10_000),
+            9_960,
+            "clamped to one round trip"
+        );
+    }
+}
@@ -0,0 +1,28 @@
+use std::fmt;
+
+use tokio_mpmc::Sender;
+
+use crate::ws_msg::WsMsg;
+
+pub type DisplayId = u32;
+
+/// A read-only projection screen attached to a room.
+pub struct DisplayEntry {
+    pub id: DisplayId,
+    pub sender: Sender<WsMsg>,
+}
+
+impl fmt::Debug for DisplayEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DisplayEntry")
+            .field("id", &self.id)
+            .field("sender len", &self.sender.len())
+            .finish()
+    }
+}
+
+impl DisplayEntry {
+    pub fn new(id: DisplayId, sender: Sender<WsMsg>) -> Self {
+        Self { id, sender }
+    }
+}
@@ -1,20 +1,41 @@
-use std::fmt;
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    fmt,
+    net::IpAddr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
+use rand::Rng;
 use serde::{Deserialize, Serialize};
+use tokio::sync::Notify;
+use tokio_mpmc::Sender;
 
 use crate::{
-    PlayerEntry,
+    PlayerEntry, UnixMs,
+    display::{DisplayEntry, DisplayId},
     host::HostEntry,
-    player::{Player, PlayerId},
-    ws_msg::WsMsg,
+    names::{MAX_NAME_CHARS, name_key},
+    player::{ConnectionStatus, PendingId, PendingJoin, PendingPlayer, Player, PlayerId},
+    rate_limit::LimitCounters,
+    tokens::{generate_token, tokens_match},
+    ws_msg::{ErrorCode, Origin, WsMsg},
 };
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Question {
+    // The web client's game builder sends "question", see SPEC 1a
+    #[serde(alias = "question", skip_serializing_if = "String::is_empty")]
     pub text: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
     pub answer: String,
     pub value: u32,
+    #[serde(default)]
     pub answered: bool,
+    #[serde(rename = "answerSeconds")]
+    pub answer_seconds: Option<u32>, // overrides the room's answer timer for this question
+    #[serde(default, rename = "dailyDouble")]
+    pub daily_double: bool,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -23,86 +44,647 @@ pub struct Category {
     pub questions: Vec<Question>,
 }
 
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Round {
+    pub categories: Vec<Category>,
+    pub multiplier: Option<u32>, // question values are scaled by this, e.g. 2 for Double Jeopardy
+}
+
+impl Round {
+    pub fn new(categories: Vec<Category>) -> Self {
+        Self {
+            categories,
+            multiplier: None,
+        }
+    }
+
+    /// What a question is actually worth this round.
+    pub fn value_of(&self, question: &Question) -> u32 {
+        question.value.saturating_mul(self.multiplier.unwrap_or(1))
+    }
+
+    /// The board as clients see it, with values already scaled by the multiplier.
+    pub fn scaled_categories(&self) -> Vec<Category> {
+        self.categories
+            .iter()
+            .map(|category| Category {
+                title: category.title.clone(),
+                questions: category
+                    .questions
+                    .iter()
+                    .map(|question| Question {
+                        value: self.value_of(question),
+                        ..question.clone()
+                    })
+                    .collect(),
+            })
+            .collect()
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FinalQuestion {
+    pub category: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub text: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub answer: String,
+}
+
+/// A player's sealed Final Jeopardy submission.
+#[derive(Clone, Debug, Default)]
+pub struct FinalResponse {
+    pub wager: Option<u32>,
+    pub answer: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct RoomSettings {
+    pub answer_seconds: Option<u32>, // time a buzzed-in player has to answer, None = no limit
+    pub buzz_seconds: Option<u32>,   // time players have to buzz in once buzzing opens
+    pub early_buzz_lockout_ms: Option<u64>, // lockout for buzzing during questionReading, None = off
+    pub buzz_arbitration_ms: Option<u64>, // how long to collect buzzes after the first, None = first wins
+    pub heartbeat_interval_ms: u64,       // 0 = no automatic heartbeats
+    pub heartbeat_jitter_ms: u64,         // random extra delay so players aren't pinged in lockstep
+    pub require_approval: bool,           // new players wait in a pending list for the host
+    pub duplicate_names: DuplicateNames,
+    pub reveal_seconds: Option<u32>, // how long the answer is shown, None = until the host continues
+    pub player_control: bool,        // the player in control picks clues, not just the host
+}
+
+/// What happens when a new player picks a name that is already in use.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum DuplicateNames {
+    #[default]
+    Suffix, // "alice" joins as "alice 2"
+    Reject,
+}
+
+impl Default for RoomSettings {
+    fn default() -> Self {
+        Self {
+            answer_seconds: None,
+            buzz_seconds: None,
+            early_buzz_lockout_ms: None,
+            buzz_arbitration_ms: None,
+            heartbeat_interval_ms: 5_000,
+            heartbeat_jitter_ms: 1_000,
+            require_approval: false,
+            duplicate_names: DuplicateNames::default(),
+            reveal_seconds: Some(5),
+            player_control: false,
+        }
+    }
+}
+
+impl RoomSettings {
+    /// How long to wait before the next automatic heartbeat, None if they are turned off.
+    pub fn next_heartbeat_delay(&self) -> Option<Duration> {
+        if self.heartbeat_interval_ms == 0 {
+            return None;
+        }
+        let jitter = rand::rng().random_range(0..=self.heartbeat_jitter_ms);
+        Some(Duration::from_millis(self.heartbeat_interval_ms + jitter))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TimerKind {
+    Answer,
+    Buzz,
+    Reveal,
+}
+
+/// A server-enforced countdown, polled by the room's ticker task.
+#[derive(Debug)]
+pub struct RoomTimer {
+    pub kind: TimerKind,
+    pub deadline: Instant,
+    last_tick: Option<u32>, // seconds left in the last TimerTick sent
+}
+
+impl RoomTimer {
+    pub fn new(kind: TimerKind, seconds: u32) -> Self {
+        Self {
+            kind,
+            deadline: Instant::now() + Duration::from_secs(seconds.into()),
+            last_tick: None,
+        }
+    }
+
+    /// Whole seconds left, rounded up so a running timer never shows 0.
+    fn seconds_left(&self) -> u32 {
+        let left = self.deadline.saturating_duration_since(Instant::now());
+        left.as_millis()
+            .div_ceil(1000)
+            .try_into()
+            .unwrap_or(u32::MAX)
+    }
+}
+
+/// A buzz collected during the arbitration window.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RankedBuzz {
+    pub pid: PlayerId,
+    pub name: String,
+    #[serde(rename = "receivedAt")]
+    pub received_at: UnixMs,
+    pub latency: u32, // how far the buzz was shifted back, receivedAt - adjustedAt
+    #[serde(rename = "adjustedAt")]
+    pub adjusted_at: UnixMs, // when the player actually pressed, by our estimate
+}
+
+/// The answer to the clue that just closed, shown during the reveal state.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Reveal {
+    pub answer: String,
+    pub pid: Option<PlayerId>, // who got it right, None if nobody did
+    pub name: Option<String>,
+}
+
+/// DoHeartbeats started under the room lock, sent once it is released.
+#[derive(Default)]
+pub struct HeartbeatBatch {
+    sends: Vec<(Sender<WsMsg>, WsMsg)>,
+}
+
+impl HeartbeatBatch {
+    pub fn len(&self) -> usize {
+        self.sends.len()
+    }
+
+    /// Sends every heartbeat, skipping players whose channel is closed or backed up
+    /// rather than waiting on them. Returns how many were skipped.
+    pub async fn send(self) -> u32 {
+        let mut failures = 0_u32;
+        for (sender, msg) in self.sends {
+            if sender.is_closed() || sender.is_full() || sender.send(msg).await.is_err() {
+                failures += 1;
+            }
+        }
+        failures
+    }
+}
+
+/// A manual score change by the host, kept in the room's audit log.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ScoreAdjustment {
+    pub pid: PlayerId,
+    pub name: String,
+    pub from: i32,
+    pub to: i32,
+    pub reason: String,
+    pub at: UnixMs,
+}
+
+/// How many rulings the host can undo.
+pub const UNDO_HISTORY: usize = 10;
+/// Reason logged for scores put back by an undo.
+const UNDO_REASON: &str = "undo";
+
+/// The parts of a room a ruling can change, saved so the host can undo it.
+#[derive(Debug)]
+struct Snapshot {
+    state: GameState,
+    current_round: usize,
+    current_question: Option<(usize, usize)>,
+    current_buzzer: Option<PlayerId>,
+    current_wager: Option<u32>,
+    board_control: Option<PlayerId>,
+    players: HashMap<PlayerId, (i32, bool)>, // score and buzzed
+    answered: Vec<Vec<Vec<bool>>>,           // per round, category and question
+    final_responses: HashMap<PlayerId, FinalResponse>,
+    final_order: Vec<PlayerId>,
+}
+
+/// Who may not join a room again, kept for the room's lifetime.
+#[derive(Debug, Default)]
+pub struct Bans {
+    tokens: Vec<String>,
+    names: HashSet<String>, // by name_key
+    ips: HashSet<IpAddr>,
+}
+
+impl Bans {
+    pub fn blocks(&self, token: Option<&str>, name: Option<&str>, ip: IpAddr) -> bool {
+        token.is_some_and(|tok| self.tokens.iter().any(|t| tokens_match(t, tok)))
+            || name.is_some_and(|name| self.names.contains(&name_key(name)))
+            || self.ips.contains(&ip)
+    }
+}
+
+/// Who a GameState is built for. Only the host sees answers and unrevealed clues.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Audience {
+    Host,
+    Player,
+    Display, // projection screens, same spoiler-free view as players
+}
+
+/// Who a message sent to the room came from.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Role {
+    Host,
+    Player(PlayerId),
+    Display(DisplayId),
+    Pending(PendingId), // waiting for the host to admit it
+}
+
+impl Role {
+    pub fn pid(&self) -> Option<PlayerId> {
+        match self {
+            Role::Player(pid) => Some(*pid),
+            _ => None,
+        }
+    }
+
+    /// Checks that this connection may send `msg`, returns what to tell it otherwise.
+    pub fn authorize(&self, msg: &WsMsg) -> Result<(), ErrorCode> {
+        match (msg.origin(), self) {
+            (Origin::Host, Role::Host) | (Origin::Player, Role::Player(_)) => Ok(()),
+            (Origin::Host, _) => Err(ErrorCode::NotHost),
+            (Origin::Player, _) => Err(ErrorCode::NotPlayer),
+            (Origin::Server, _) => Err(ErrorCode::ServerOnly),
+        }
+    }
+}
+
 pub struct Room {
     pub code: String,
     pub host_token: String,
+    pub display_token: String,
     pub state: GameState,
     pub host: Option<HostEntry>,
+    pub displays: Vec<DisplayEntry>,
+    next_display_id: DisplayId,
     pub players: Vec<PlayerEntry>,
-    pub categories: Vec<Category>,
+    pub rounds: Vec<Round>,
+    pub current_round: usize,
     pub current_question: Option<(usize, usize)>, // (category_index, question_index)
     pub current_buzzer: Option<PlayerId>,
+    pub current_wager: Option<u32>,
+    pub board_control: Option<PlayerId>, // last correct responder unless the host handed it over
+    pub final_question: Option<FinalQuestion>,
+    pub final_responses: HashMap<PlayerId, FinalResponse>, // only players eligible for Final Jeopardy
+    pub final_order: Vec<PlayerId>,                        // reveal order, lowest score first
+    pub reveal: Option<Reveal>,                            // only while in the reveal state
+    pub settings: RoomSettings,
+    pub timer: Option<RoomTimer>,
+    pub pending_buzzes: Vec<RankedBuzz>,
+    pub arbitration_deadline: Option<UnixMs>,
+    pub bans: Bans,
+    pub join_password: Option<String>,
+    pub pending: Vec<PendingJoin>, // joins waiting for approval
+    pub limit_counters: Arc<LimitCounters>,
+    pub score_log: Vec<ScoreAdjustment>, // every manual score change, oldest first
+    pub wake: Arc<Notify>,               // makes the room's ticker look at its deadlines again
+    pub last_activity: Instant,          // last message or connection, idle rooms are closed
+    history: VecDeque<Snapshot>,         // before each ruling, newest last
+    next_heartbeat: Option<Instant>, // next automatic heartbeat round, None while nobody is connected
+    next_player_id: PlayerId,
+    next_pending_id: PendingId,
 }
 
+/// Smallest wager accepted on a Daily Double.
+pub const MIN_DAILY_DOUBLE_WAGER: u32 = 5;
+
 impl fmt::Debug for Room {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("Room")
             .field("code", &self.code)
-            .field("host_token", &self.host_token)
+            .field("host_token", &"<redacted>")
+            .field("display_token", &"<redacted>")
             .field("host", &self.host)
+            .field("displays", &self.displays)
             .field("state", &self.state)
             .field("players", &self.players)
-            .field("category count", &self.categories.len())
+            .field("round count", &self.rounds.len())
+            .field("current round", &self.current_round)
             .field("current question", &self.current_question)
             .field("current buzzer", &self.current_buzzer)
+            .field("current wager", &self.current_wager)
+            .field("board control", &self.board_control)
+            .field("final question", &self.final_question)
+            .field("final responses", &self.final_responses)
+            .field("final order", &self.final_order)
+            .field("settings", &self.settings)
+            .field("timer", &self.timer)
+            .field("pending buzzes", &self.pending_buzzes)
+            .field("arbitration deadline", &self.arbitration_deadline)
+            .field("bans", &self.bans)
+            .field("pending", &self.pending)
             .finish()
     }
 }
 
 impl Room {
-    pub fn new(code: String, host_token: String) -> Self {
+    pub fn new(code: String, host_token: String, display_token: String) -> Self {
         Self {
             code,
             host_token,
+            display_token,
             state: GameState::default(),
             host: None,
+            displays: Vec::new(),
+            next_display_id: 0,
             players: Vec::new(),
-            categories: Vec::new(),
+            rounds: Vec::new(),
+            current_round: 0,
             current_question: None,
             current_buzzer: None,
+            current_wager: None,
+            board_control: None,
+            final_question: None,
+            final_responses: HashMap::new(),
+            final_order: Vec::new(),
+            reveal: None,
+            settings: RoomSettings::default(),
+            timer: None,
+            pending_buzzes: Vec::new(),
+            arbitration_deadline: None,
+            bans: Bans::default(),
+            join_password: None,
+            pending: Vec::new(),
+            limit_counters: Arc::default(),
+            score_log: Vec::new(),
+            wake: Arc::default(),
+            last_activity: Instant::now(),
+            history: VecDeque::new(),
+            next_heartbeat: None,
+            next_player_id: 1,
+            next_pending_id: 0,
         }
     }
 }
 
 impl Room {
-    pub async fn broadcast_state(&self) -> anyhow::Result<()> {
+    pub fn game_state_msg(&self, audience: Audience) -> WsMsg {
         let players: Vec<Player> = self.players.iter().map(|e| e.player.clone()).collect();
+        let mut categories = self
+            .round()
+            .map(Round::scaled_categories)
+            .unwrap_or_default();
+        let mut final_question = self.final_question.clone();
+
+        if audience != Audience::Host {
+            let revealed = match self.state {
+                GameState::QuestionReading
+                | GameState::WaitingForBuzz
+                | GameState::Answer
+                | GameState::Reveal => self.current_question,
+                _ => None,
+            };
+            let answered = match self.state {
+                GameState::Reveal => self.current_question,
+                _ => None,
+            };
+            // Daily Doubles stay hidden until one is picked
+            let wagering = match self.state {
+                GameState::Wager => self.current_question,
+                _ => None,
+            };
+            for (cat_idx, category) in categories.iter_mut().enumerate() {
+                for (q_idx, question) in category.questions.iter_mut().enumerate() {
+                    let here = Some((cat_idx, q_idx));
+                    if answered != here {
+                        question.answer.clear();
+                    }
+                    if revealed != here {
+                        question.text.clear();
+                    }
+                    if revealed != here && wagering != here {
+                        question.daily_double = false;
+                    }
+                }
+            }
+
+            final_question = match self.state {
+                GameState::FinalCategory => final_question.map(|q| FinalQuestion {
+                    text: String::new(),
+                    answer: String::new(),
+                    ..q
+                }),
+                GameState::FinalClue => final_question.map(|q| FinalQuestion {
+                    answer: String::new(),
+                    ..q
+                }),
+                // Responses are being revealed, the answer is no longer a secret
+                GameState::FinalReveal | GameState::GameEnd => final_question,
+                _ => None,
+            };
+        }
 
-        let msg = WsMsg::GameState {
+        WsMsg::GameState {
             state: self.state.clone(),
-            categories: self.categories.clone(),
-            players: players.clone(),
+            round: self.current_round,
+            categories,
+            players,
             current_buzzer: self.current_buzzer,
             current_question: self.current_question,
-        };
+            final_question,
+            reveal: self.reveal.clone(),
+            board_control: self.selecting_player(),
+        }
+    }
 
+    /// Sends each audience its own view of the game.
+    pub async fn broadcast_state(&self) -> anyhow::Result<()> {
         if let Some(host) = &self.host {
-            host.sender.send(msg.clone()).await?;
+            host.sender
+                .send(self.game_state_msg(Audience::Host))
+                .await?;
+        }
+        let msg = self.game_state_msg(Audience::Player);
+        for player_entry in &self.players {
+            let _ = player_entry.sender.send(msg.clone()).await;
         }
+        let msg = self.game_state_msg(Audience::Display);
+        for display in &self.displays {
+            let _ = display.sender.send(msg.clone()).await;
+        }
+        Ok(())
+    }
 
+    /// Sends a message to the host, every player and every display.
+    pub async fn broadcast(&self, msg: WsMsg) -> anyhow::Result<()> {
+        if let Some(host) = &self.host {
+            host.sender.send(msg.clone()).await?;
+        }
         for player_entry in &self.players {
             let _ = player_entry.sender.send(msg.clone()).await;
         }
+        for display in &self.displays {
+            let _ = display.sender.send(msg.clone()).await;
+        }
+        Ok(())
+    }
+
+    /// Picks the name a new player actually gets, compared case-insensitively with
+    /// everyone already seated or waiting for approval.
+    pub fn claim_name(&self, name: &str) -> Result<String, ErrorCode> {
+        let taken: HashSet<String> = self
+            .players
+            .iter()
+            .map(|p| name_key(&p.player.name))
+            .chain(self.pending.iter().map(|p| name_key(&p.pending.name)))
+            .collect();
+        if !taken.contains(&name_key(name)) {
+            return Ok(name.to_string());
+        }
+        if self.settings.duplicate_names == DuplicateNames::Reject {
+            return Err(ErrorCode::NameTaken);
+        }
+        (2..)
+            .map(|n| {
+                let suffix = format!(" {n}");
+                let base: String = name
+                    .chars()
+                    .take(MAX_NAME_CHARS.saturating_sub(suffix.len()))
+                    .collect();
+                format!("{}{suffix}", base.trim_end())
+            })
+            .find(|candidate| !taken.contains(&name_key(candidate)))
+            .ok_or(ErrorCode::NameTaken)
+    }
+
+    /// Pids are never reused, even after a player is kicked.
+    pub fn next_player_id(&mut self) -> PlayerId {
+        let pid = self.next_player_id;
+        self.next_player_id += 1;
+        pid
+    }
+
+    /// Parks a new connection until the host decides on it.
+    pub fn add_pending(&mut self, name: String, sender: Sender<WsMsg>, ip: IpAddr) -> PendingId {
+        let id = self.next_pending_id;
+        self.next_pending_id = self.next_pending_id.wrapping_add(1);
+        self.pending.push(PendingJoin {
+            pending: PendingPlayer { id, name },
+            sender,
+            ip,
+        });
+        id
+    }
+
+    /// Forgets a pending join, returns false if it was already gone.
+    pub fn remove_pending(&mut self, id: PendingId) -> bool {
+        let before = self.pending.len();
+        self.pending.retain(|p| p.pending.id != id);
+        before != self.pending.len()
+    }
+
+    pub fn pending_list_msg(&self) -> WsMsg {
+        WsMsg::PendingList(self.pending.iter().map(|p| p.pending.clone()).collect())
+    }
+
+    /// Turns a pending join into a player and hands its connection the NewPlayer.
+    async fn admit_player(&mut self, id: PendingId) -> anyhow::Result<()> {
+        let Some(idx) = self.pending.iter().position(|p| p.pending.id == id) else {
+            return Ok(());
+        };
+        let join = self.pending.remove(idx);
+        let pid = self.next_player_id();
+        let token = generate_token();
+        let mut entry = PlayerEntry::new(
+            Player::new(pid, join.pending.name),
+            token.clone(),
+            join.sender.clone(),
+        );
+        entry.ip = Some(join.ip);
+        self.players.push(entry);
+        // A first connected player starts the heartbeat rounds
+        self.wake.notify_one();
+        let _ = join.sender.send(WsMsg::NewPlayer { pid, token }).await;
+
+        if let Some(host) = &self.host {
+            host.sender.send(self.player_list_msg()).await?;
+            host.sender.send(self.pending_list_msg()).await?;
+        }
+        Ok(())
+    }
+
+    /// Moves the seat this token belongs to onto a new connection: the old session is ended,
+    /// the token rotated, and the new connection gets NewPlayer and its PlayerState. With a
+    /// pid, the token must be that seat's. None if the token matches no seat.
+    pub async fn reconnect_player(
+        &mut self,
+        pid: Option<PlayerId>,
+        token: &str,
+        sender: &Sender<WsMsg>,
+        ip: IpAddr,
+    ) -> anyhow::Result<Option<PlayerId>> {
+        let Some(existing) = self
+            .players
+            .iter_mut()
+            .find(|p| pid.is_none_or(|pid| p.player.pid == pid) && tokens_match(&p.token, token))
+        else {
+            return Ok(None);
+        };
+        end_session(&existing.sender, ErrorCode::SessionReplaced).await;
+        existing.sender = sender.clone();
+        existing.ip = Some(ip);
+        existing.player.status = ConnectionStatus::Connected;
+        existing.token = generate_token();
+        sender
+            .send(WsMsg::NewPlayer {
+                pid: existing.player.pid,
+                token: existing.token.clone(),
+            })
+            .await?;
+        sender.send(existing.state_msg(&self.state)).await?;
+        Ok(Some(existing.player.pid))
+    }
+
+    async fn reject_player(&mut self, id: PendingId, reason: &str) -> anyhow::Result<()> {
+        let Some(idx) = self.pending.iter().position(|p| p.pending.id == id) else {
+            return Ok(());
+        };
+        let join = self.pending.remove(idx);
+        let reason = if reason.is_empty() {
+            "The host declined your request to join".to_string()
+        } else {
+            reason.to_string()
+        };
+        if !join.sender.is_full() {
+            let _ = join.sender.send(WsMsg::JoinRejected { reason }).await;
+        }
+        join.sender.close();
 
+        if let Some(host) = &self.host {
+            host.sender.send(self.pending_list_msg()).await?;
+        }
         Ok(())
     }
 
-    pub async fn broadcast_player_states(&self) -> anyhow::Result<()> {
-        let can_buzz_state = self.state == GameState::WaitingForBuzz;
+    pub fn attach_display(&mut self, sender: Sender<WsMsg>) -> DisplayId {
+        let id = self.next_display_id;
+        self.next_display_id = self.next_display_id.wrapping_add(1);
+        self.displays.push(DisplayEntry::new(id, sender));
+        id
+    }
+
+    pub fn detach_display(&mut self, id: DisplayId) {
+        self.displays.retain(|d| d.id != id);
+    }
 
+    pub async fn broadcast_player_states(&self) -> anyhow::Result<()> {
         for player_entry in &self.players {
-            let player_state_msg = WsMsg::PlayerState {
-                pid: player_entry.player.pid,
-                buzzed: player_entry.player.buzzed,
-                score: player_entry.player.score,
-                can_buzz: can_buzz_state && !player_entry.player.buzzed,
-            };
+            let player_state_msg = player_entry.state_msg(&self.state);
             let _ = player_entry.sender.send(player_state_msg).await;
         }
         Ok(())
     }
 
-    pub async fn update(&mut self, msg: &WsMsg, pid: Option<PlayerId>) -> anyhow::Result<()> {
+    pub async fn update(&mut self, msg: &WsMsg, role: Role) -> anyhow::Result<()> {
+        self.touch();
+        if let Err(code) = role.authorize(msg) {
+            self.send_to(role, WsMsg::error(code)).await;
+            return Ok(());
+        }
+        let pid = role.pid();
         let own_entry: Option<&mut PlayerEntry> = if let Some(pid) = pid {
             let idx = self.players.iter().position(|p| p.player.pid == pid);
             idx.map(|i| &mut self.players[i])
@@ -111,159 +693,1087 @@ impl Room {
         };
         match msg {
             WsMsg::StartGame {} => {
-                self.state = GameState::Selection;
+                self.set_state(GameState::Selection);
                 self.broadcast_state().await?;
                 self.broadcast_player_states().await?;
             }
 
+            // Always allowed, the host can pick for whoever is in control
             WsMsg::HostChoice {
                 category_index,
                 question_index,
             } => {
-                self.current_question = Some((*category_index, *question_index));
-                self.current_buzzer = None;
-                // Reset all player buzz states
-                for player in &mut self.players {
-                    player.player.buzzed = false;
+                self.choose_question(*category_index, *question_index, role)
+                    .await?;
+            }
+
+            WsMsg::PlayerChoice {
+                category_index,
+                question_index,
+            } => {
+                if self.settings.player_control && pid == self.selecting_player() {
+                    self.choose_question(*category_index, *question_index, role)
+                        .await?;
+                } else {
+                    self.send_to(role, WsMsg::error(ErrorCode::NotInControl))
+                        .await;
+                }
+            }
+
+            WsMsg::SetControl { pid } => {
+                self.hand_over_control(*pid).await?;
+            }
+
+            WsMsg::Wager { .. } if self.state != GameState::Wager || pid != self.current_buzzer => {
+                // Only the player asked for a Daily Double wager may send one
+                if let Some(player_id) = pid {
+                    let rejected = WsMsg::WagerRejected {
+                        min_wager: 0,
+                        max_wager: 0,
+                    };
+                    self.send_to_player(player_id, rejected).await;
+                }
+            }
+
+            WsMsg::Wager { amount } => {
+                if let Some(player_id) = pid {
+                    let max_wager = self.max_wager(player_id);
+                    if (MIN_DAILY_DOUBLE_WAGER..=max_wager).contains(amount) {
+                        self.current_wager = Some(*amount);
+                        self.set_state(GameState::QuestionReading);
+
+                        let accepted = WsMsg::WagerAccepted {
+                            pid: player_id,
+                            amount: *amount,
+                        };
+                        if let Some(host) = &self.host {
+                            host.sender.send(accepted.clone()).await?;
+                        }
+                        self.send_to_player(player_id, accepted).await;
+
+                        self.broadcast_state().await?;
+                        self.broadcast_player_states().await?;
+                    } else {
+                        let rejected = WsMsg::WagerRejected {
+                            min_wager: MIN_DAILY_DOUBLE_WAGER,
+                            max_wager,
+                        };
+                        self.send_to_player(player_id, rejected).await;
+                    }
                 }
-                self.state = GameState::QuestionReading;
-                self.broadcast_state().await?;
-                self.broadcast_player_states().await?;
             }
 
             WsMsg::HostReady {} => {
-                self.state = GameState::WaitingForBuzz;
+                match self.state {
+                    // Reveal the clue; anyone who didn't wager in time wagers nothing
+                    GameState::FinalCategory => {
+                        for response in self.final_responses.values_mut() {
+                            response.wager.get_or_insert(0);
+                        }
+                        self.set_state(GameState::FinalClue);
+                    }
+                    // Lock answers and start revealing them
+                    GameState::FinalClue => {
+                        self.set_state(GameState::FinalReveal);
+                        self.broadcast_state().await?;
+                        self.broadcast_player_states().await?;
+                        self.reveal_next_final().await?;
+                        return Ok(());
+                    }
+                    // Done showing the answer
+                    GameState::Reveal => {
+                        self.finish_question();
+                    }
+                    // A Daily Double goes straight to the wagering player, no buzzing
+                    GameState::QuestionReading if self.current_wager.is_some() => {
+                        self.set_state(GameState::Answer);
+                    }
+                    GameState::QuestionReading => {
+                        self.lock_out_early_buzzers(PlayerEntry::time_ms());
+                        self.set_state(GameState::WaitingForBuzz);
+                    }
+                    // Nothing to move on from, e.g. a wager is still outstanding
+                    _ => {
+                        self.send_to(role, WsMsg::error(ErrorCode::WrongState))
+                            .await;
+                        return Ok(());
+                    }
+                }
                 self.broadcast_state().await?;
                 self.broadcast_player_states().await?;
             }
 
-            WsMsg::Buzz {} => {
-                if self.state == GameState::WaitingForBuzz
+            WsMsg::FinalWager { amount } => {
+                if self.state == GameState::FinalCategory
                     && let Some(player_id) = pid
-                    && let Some(player_entry) =
-                        self.players.iter_mut().find(|p| p.player.pid == player_id)
-                    && !player_entry.player.buzzed
+                    && let Some(score) = self
+                        .players
+                        .iter()
+                        .find(|p| p.player.pid == player_id)
+                        .map(|p| p.player.score)
+                    && let Some(response) = self.final_responses.get_mut(&player_id)
                 {
-                    player_entry.player.buzzed = true;
-                    self.current_buzzer = Some(player_id);
-                    self.state = GameState::Answer;
-
-                    if let Some(host) = &self.host {
-                        let buzzed_msg = WsMsg::Buzzed {
-                            pid: player_id,
-                            name: player_entry.player.name.clone(),
+                    let max_wager = u32::try_from(score).unwrap_or(0);
+                    if *amount <= max_wager {
+                        response.wager = Some(*amount);
+                        self.send_final_submitted(player_id).await?;
+                    } else {
+                        let rejected = WsMsg::WagerRejected {
+                            min_wager: 0,
+                            max_wager,
                         };
-                        host.sender.send(buzzed_msg).await?;
+                        self.send_to_player(player_id, rejected).await;
                     }
+                }
+            }
 
-                    self.broadcast_state().await?;
-                    self.broadcast_player_states().await?;
+            WsMsg::FinalAnswer { answer } => {
+                if self.state == GameState::FinalClue
+                    && let Some(player_id) = pid
+                    && let Some(response) = self.final_responses.get_mut(&player_id)
+                {
+                    response.answer = Some(answer.clone());
+                    self.send_final_submitted(player_id).await?;
                 }
             }
 
-            WsMsg::HostChecked { correct } => {
-                if let Some((cat_idx, q_idx)) = self.current_question {
-                    if *correct {
-                        if let Some(category) = self.categories.get_mut(cat_idx)
-                            && let Some(question) = category.questions.get_mut(q_idx)
-                        {
-                            question.answered = true;
-
-                            if let Some(buzzer_id) = self.current_buzzer
-                                && let Some(player) =
-                                    self.players.iter_mut().find(|p| p.player.pid == buzzer_id)
-                            {
-                                let incr: i32 = question.value.try_into()?;
-                                player.player.score += incr;
-                            }
-                        }
-                        self.current_question = None;
-                        self.current_buzzer = None;
+            WsMsg::Buzz { .. } if self.state == GameState::QuestionReading => {
+                if self.settings.early_buzz_lockout_ms.is_some()
+                    && let Some(player_id) = pid
+                    && let Some(player_entry) =
+                        self.players.iter_mut().find(|p| p.player.pid == player_id)
+                {
+                    player_entry.buzzed_early = true;
+                }
+            }
 
-                        if self.has_remaining_questions() {
-                            self.state = GameState::Selection;
-                        } else {
-                            self.state = GameState::GameEnd;
+            WsMsg::Buzz { t_pressed } => {
+                let now = PlayerEntry::time_ms();
+                if self.state == GameState::WaitingForBuzz
+                    && let Some(player_id) = pid
+                    && let Some(player_entry) =
+                        self.players.iter_mut().find(|p| p.player.pid == player_id)
+                    && !player_entry.player.buzzed
+                    && !player_entry.is_locked_out(now)
+                {
+                    if let Some(window_ms) = self.settings.buzz_arbitration_ms {
+                        if !self.pending_buzzes.iter().any(|b| b.pid == player_id) {
+                            let adjusted_at = player_entry.press_time(*t_pressed, now);
+                            self.pending_buzzes.push(RankedBuzz {
+                                pid: player_id,
+                                name: player_entry.player.name.clone(),
+                                received_at: now,
+                                latency: now
+                                    .saturating_sub(adjusted_at)
+                                    .try_into()
+                                    .unwrap_or(u32::MAX),
+                                adjusted_at,
+                            });
+                            if self.arbitration_deadline.is_none() {
+                                self.arbitration_deadline = Some(now + window_ms);
+                                self.wake.notify_one();
+                            }
                         }
                     } else {
-                        if let Some(category) = self.categories.get(cat_idx)
-                            && let Some(question) = category.questions.get(q_idx)
-                            && let Some(buzzer_id) = self.current_buzzer
-                            && let Some(player) =
-                                self.players.iter_mut().find(|p| p.player.pid == buzzer_id)
-                        {
-                            let decr: i32 = question.value.try_into()?;
-                            player.player.score -= decr;
-                        }
-                        let any_can_buzz = self.players.iter().any(|p| !p.player.buzzed);
-                        if any_can_buzz {
-                            self.current_buzzer = None;
-                            self.state = GameState::WaitingForBuzz;
-                        } else {
-                            if let Some(category) = self.categories.get_mut(cat_idx)
-                                && let Some(question) = category.questions.get_mut(q_idx)
-                            {
-                                question.answered = true;
-                            }
-                            self.current_question = None;
-                            self.current_buzzer = None;
+                        self.award_buzz(player_id).await?;
+                    }
+                }
+            }
 
-                            if self.has_remaining_questions() {
-                                self.state = GameState::Selection;
-                            } else {
-                                self.state = GameState::GameEnd;
-                            }
+            WsMsg::HostChecked { correct } if self.state == GameState::FinalReveal => {
+                self.save_snapshot();
+                if !self.final_order.is_empty() {
+                    let player_id = self.final_order.remove(0);
+                    let wager = self
+                        .final_responses
+                        .get(&player_id)
+                        .and_then(|r| r.wager)
+                        .unwrap_or(0);
+                    if let Some(player) =
+                        self.players.iter_mut().find(|p| p.player.pid == player_id)
+                    {
+                        let delta: i32 = wager.try_into()?;
+                        if *correct {
+                            player.player.score += delta;
+                        } else {
+                            player.player.score -= delta;
                         }
                     }
                 }
+                if self.final_order.is_empty() {
+                    self.set_state(GameState::GameEnd);
+                }
                 self.broadcast_state().await?;
                 self.broadcast_player_states().await?;
+                self.reveal_next_final().await?;
             }
 
-            WsMsg::EndGame {} => {
-                self.state = GameState::GameEnd;
-                self.broadcast_state().await?;
-                self.broadcast_player_states().await?;
+            WsMsg::HostChecked { .. } if self.state != GameState::Answer => {
+                self.send_to(role, WsMsg::error(ErrorCode::NothingToJudge))
+                    .await;
             }
-            WsMsg::Heartbeat { hbid, t_dohb_recv } => {
-                if let Some(entry) = own_entry {
-                    if entry.on_know_dohb_recv(*hbid, *t_dohb_recv) {
-                        println!("successfully updated dohb_recv");
-                    } else {
-                        println!("WARN: failed to update DoHeartbeat recv time");
-                    }
-                } else {
-                    println!("WARN: own entry missing handling Heartbeat, continuing anyway");
-                }
+
+            WsMsg::HostChecked { correct } => {
+                self.judge_answer(*correct).await?;
             }
-            WsMsg::LatencyOfHeartbeat { hbid, t_lat } => {
-                if let Some(entry) = own_entry {
-                    if !entry.on_latencyhb(
-                        *hbid,
-                        (*t_lat).try_into().expect(
-                            "LatencyOfHeartbeat latency of heartbeat exceeds 32-bit integer limit",
-                        ),
-                    ) {
-                        println!(
-                            "WARN: handling LatencyOfHeartbeat failed to update latencies, continuing anyway"
-                        );
+
+            WsMsg::KickPlayer { pid, reason } => {
+                self.remove_player(*pid, reason, false).await?;
+            }
+
+            WsMsg::BanPlayer {
+                pid,
+                reason,
+                by_name,
+                by_ip,
+            } => {
+                if let Some(entry) = self.players.iter().find(|p| p.player.pid == *pid) {
+                    self.bans.tokens.push(entry.token.clone());
+                    if *by_name {
+                        self.bans.names.insert(name_key(&entry.player.name));
+                    }
+                    if *by_ip && let Some(ip) = entry.ip {
+                        self.bans.ips.insert(ip);
                     }
-                } else {
-                    println!(
-                        "WARN: own entry missing while handling LatencyOfHeartbeat, continuing anyway"
-                    );
                 }
+                self.remove_player(*pid, reason, true).await?;
             }
-            _ => {}
-        }
 
-        Ok(())
-    }
+            WsMsg::AdmitPlayer { id } => {
+                self.admit_player(*id).await?;
+            }
 
-    fn has_remaining_questions(&self) -> bool {
-        self.categories
+            WsMsg::RejectPlayer { id, reason } => {
+                self.reject_player(*id, reason).await?;
+            }
+
+            WsMsg::SetScore { pid, score, reason } => {
+                self.change_score(*pid, |_| *score, reason).await?;
+            }
+
+            WsMsg::AdjustScore { pid, delta, reason } => {
+                self.change_score(*pid, |old| old.saturating_add(*delta), reason)
+                    .await?;
+            }
+
+            WsMsg::GetScoreLog {} => {
+                let entries = self.score_log.clone();
+                self.send_to(role, WsMsg::ScoreLog { entries }).await;
+            }
+
+            WsMsg::VoidClue { return_to_board } => {
+                self.void_clue(*return_to_board).await?;
+            }
+
+            WsMsg::Undo {} => {
+                let undone = self.undo().await?;
+                if !undone {
+                    self.send_to(role, WsMsg::error(ErrorCode::NothingToUndo))
+                        .await;
+                }
+            }
+
+            WsMsg::EndGame {} => {
+                self.set_state(GameState::GameEnd);
+                self.broadcast_state().await?;
+                self.broadcast_player_states().await?;
+            }
+            WsMsg::Heartbeat {
+                hbid,
+                t_dohb_recv,
+                t_hb_sent,
+            } => {
+                if let Some(entry) = own_entry {
+                    let t_recv = PlayerEntry::time_ms();
+                    // Older clients reply immediately and don't report when they sent
+                    let t_hb_sent = t_hb_sent.unwrap_or(*t_dohb_recv);
+                    if entry.on_heartbeat(*hbid, *t_dohb_recv, t_hb_sent, t_recv) {
+                        let msg = WsMsg::PlayerClock {
+                            pid: entry.player.pid,
+                            clock: entry.clock_stats(),
+                        };
+                        if let Some(host) = &self.host {
+                            host.sender.send(msg).await?;
+                        }
+                    } else {
+                        println!("WARN: heartbeat {hbid} unknown, expired or an outlier");
+                    }
+                } else {
+                    println!("WARN: own entry missing handling Heartbeat, continuing anyway");
+                }
+            }
+            // Superseded by the clock estimator, still accepted from older clients
+            WsMsg::LatencyOfHeartbeat { .. } => {}
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    /// Gives board control to a seated player, regardless of who answered last.
+    async fn hand_over_control(&mut self, pid: PlayerId) -> anyhow::Result<()> {
+        if self.players.iter().any(|p| p.player.pid == pid) {
+            self.board_control = Some(pid);
+            self.broadcast_state().await?;
+        }
+        Ok(())
+    }
+
+    /// Opens a clue picked by the host or the player in control. Refused unless the board is
+    /// showing and the clue is still on it; a running reveal is ended first, and if that
+    /// moves on to the next round the pick is refused too.
+    async fn choose_question(
+        &mut self,
+        category_index: usize,
+        question_index: usize,
+        role: Role,
+    ) -> anyhow::Result<()> {
+        let was_revealing = self.state == GameState::Reveal;
+        let round = self.current_round;
+        if was_revealing {
+            self.finish_question();
+        }
+        // Indices picked on the old board mean nothing on the next round's
+        let available = self.state == GameState::Selection
+            && self.current_round == round
+            && self
+                .question_at(category_index, question_index)
+                .is_some_and(|q| !q.answered);
+        if !available {
+            self.send_to(role, WsMsg::error(ErrorCode::InvalidChoice))
+                .await;
+            if was_revealing {
+                self.broadcast_state().await?;
+                self.broadcast_player_states().await?;
+            }
+            return Ok(());
+        }
+        // Rulings on earlier clues can't be undone once the next one is open
+        self.history.clear();
+        self.current_question = Some((category_index, question_index));
+        self.current_buzzer = None;
+        self.current_wager = None;
+        // Reset all player buzz states
+        for player in &mut self.players {
+            player.player.buzzed = false;
+            player.buzzed_early = false;
+            player.locked_until = None;
+        }
+        let daily_double = self
+            .question_at(category_index, question_index)
+            .is_some_and(|q| q.daily_double);
+        if daily_double && let Some(wagerer) = self.selecting_player() {
+            // Only the selecting player answers a Daily Double, so they hold the buzz
+            self.current_buzzer = Some(wagerer);
+            self.set_state(GameState::Wager);
+            self.broadcast_state().await?;
+            self.broadcast_player_states().await?;
+
+            let request = WsMsg::DailyDouble {
+                pid: wagerer,
+                min_wager: MIN_DAILY_DOUBLE_WAGER,
+                max_wager: self.max_wager(wagerer),
+            };
+            if let Some(host) = &self.host {
+                host.sender.send(request.clone()).await?;
+            }
+            self.send_to_player(wagerer, request).await;
+        } else {
+            self.set_state(GameState::QuestionReading);
+            self.broadcast_state().await?;
+            self.broadcast_player_states().await?;
+        }
+        Ok(())
+    }
+
+    /// Applies the host's ruling on the current answer (also used when the answer timer expires).
+    /// Does nothing outside the answer state, so a clue is never judged twice.
+    async fn judge_answer(&mut self, correct: bool) -> anyhow::Result<()> {
+        if self.state != GameState::Answer {
+            return Ok(());
+        }
+        if let Some((cat_idx, q_idx)) = self.current_question {
+            self.save_snapshot();
+            if let Some(wager) = self.current_wager {
+                if let Some(buzzer_id) = self.current_buzzer
+                    && let Some(player) =
+                        self.players.iter_mut().find(|p| p.player.pid == buzzer_id)
+                {
+                    let delta: i32 = wager.try_into()?;
+                    if correct {
+                        player.player.score += delta;
+                    } else {
+                        player.player.score -= delta;
+                    }
+                }
+                if correct && let Some(buzzer_id) = self.current_buzzer {
+                    self.board_control = Some(buzzer_id);
+                }
+                let winner = self.current_buzzer.filter(|_| correct);
+                self.start_reveal(winner).await?;
+            } else if correct {
+                if let Some(value) = self.question_value(cat_idx, q_idx)
+                    && let Some(buzzer_id) = self.current_buzzer
+                    && let Some(player) =
+                        self.players.iter_mut().find(|p| p.player.pid == buzzer_id)
+                {
+                    let incr: i32 = value.try_into()?;
+                    player.player.score += incr;
+                }
+                if let Some(buzzer_id) = self.current_buzzer {
+                    self.board_control = Some(buzzer_id);
+                }
+                self.start_reveal(self.current_buzzer).await?;
+            } else {
+                if let Some(value) = self.question_value(cat_idx, q_idx)
+                    && let Some(buzzer_id) = self.current_buzzer
+                    && let Some(player) =
+                        self.players.iter_mut().find(|p| p.player.pid == buzzer_id)
+                {
+                    let decr: i32 = value.try_into()?;
+                    player.player.score -= decr;
+                }
+                let any_can_buzz = self.players.iter().any(|p| !p.player.buzzed);
+                if any_can_buzz {
+                    self.current_buzzer = None;
+                    self.set_state(GameState::WaitingForBuzz);
+                } else {
+                    self.start_reveal(None).await?;
+                }
+            }
+        }
+        self.broadcast_state().await?;
+        self.broadcast_player_states().await?;
+        Ok(())
+    }
+
+    /// Closes a broken clue without scoring anyone, either back on the board or marked answered.
+    async fn void_clue(&mut self, return_to_board: bool) -> anyhow::Result<()> {
+        let open = matches!(
+            self.state,
+            GameState::Wager
+                | GameState::QuestionReading
+                | GameState::WaitingForBuzz
+                | GameState::Answer
+        );
+        if !open || self.current_question.is_none() {
+            return Ok(());
+        }
+        self.save_snapshot();
+        for player in &mut self.players {
+            player.player.buzzed = false;
+            player.buzzed_early = false;
+            player.locked_until = None;
+        }
+        if return_to_board {
+            self.current_question = None;
+            self.current_buzzer = None;
+            self.current_wager = None;
+            self.set_state(GameState::Selection);
+        } else {
+            self.finish_question();
+        }
+        self.broadcast_state().await?;
+        self.broadcast_player_states().await
+    }
+
+    /// Remembers the room as it is now, so the next ruling can be undone.
+    fn save_snapshot(&mut self) {
+        if self.history.len() == UNDO_HISTORY {
+            self.history.pop_front();
+        }
+        let answered = self
+            .rounds
+            .iter()
+            .map(|round| {
+                round
+                    .categories
+                    .iter()
+                    .map(|cat| cat.questions.iter().map(|q| q.answered).collect())
+                    .collect()
+            })
+            .collect();
+        self.history.push_back(Snapshot {
+            state: self.state.clone(),
+            current_round: self.current_round,
+            current_question: self.current_question,
+            current_buzzer: self.current_buzzer,
+            current_wager: self.current_wager,
+            board_control: self.board_control,
+            players: self
+                .players
+                .iter()
+                .map(|p| (p.player.pid, (p.player.score, p.player.buzzed)))
+                .collect(),
+            answered,
+            final_responses: self.final_responses.clone(),
+            final_order: self.final_order.clone(),
+        });
+    }
+
+    /// Puts the room back the way it was before the last ruling and tells everyone.
+    /// Returns false if there was nothing to undo.
+    async fn undo(&mut self) -> anyhow::Result<bool> {
+        let Some(snapshot) = self.history.pop_back() else {
+            return Ok(false);
+        };
+        for (round, answered) in self.rounds.iter_mut().zip(&snapshot.answered) {
+            for (category, answered) in round.categories.iter_mut().zip(answered) {
+                for (question, answered) in category.questions.iter_mut().zip(answered) {
+                    question.answered = *answered;
+                }
+            }
+        }
+        // Players who joined since keep their seat as is, removed ones stay removed.
+        // Reverted scores are logged, they may undo a manual change made since.
+        let at = PlayerEntry::time_ms();
+        for entry in &mut self.players {
+            if let Some((score, buzzed)) = snapshot.players.get(&entry.player.pid) {
+                if entry.player.score != *score {
+                    self.score_log.push(ScoreAdjustment {
+                        pid: entry.player.pid,
+                        name: entry.player.name.clone(),
+                        from: entry.player.score,
+                        to: *score,
+                        reason: UNDO_REASON.to_string(),
+                        at,
+                    });
+                }
+                entry.player.score = *score;
+                entry.player.buzzed = *buzzed;
+            }
+        }
+        let present: HashSet<PlayerId> = self.players.iter().map(|p| p.player.pid).collect();
+        let present = |pid: &PlayerId| present.contains(pid);
+        self.current_round = snapshot.current_round;
+        self.current_question = snapshot.current_question;
+        self.current_buzzer = snapshot.current_buzzer.filter(present);
+        self.current_wager = snapshot.current_wager;
+        self.board_control = snapshot.board_control.filter(present);
+        self.final_responses = snapshot.final_responses;
+        self.final_responses.retain(|pid, _| present(pid));
+        self.final_order = snapshot.final_order;
+        self.final_order.retain(present);
+        self.set_state(snapshot.state);
+
+        self.broadcast_state().await?;
+        self.broadcast_player_states().await?;
+        if self.state == GameState::FinalReveal {
+            self.reveal_next_final().await?;
+        }
+        Ok(true)
+    }
+
+    /// Applies a host's manual score change, logs it and shows everyone the new score.
+    async fn change_score(
+        &mut self,
+        pid: PlayerId,
+        new_score: impl FnOnce(i32) -> i32,
+        reason: &str,
+    ) -> anyhow::Result<()> {
+        let Some(entry) = self.players.iter_mut().find(|p| p.player.pid == pid) else {
+            return Ok(());
+        };
+        let from = entry.player.score;
+        entry.player.score = new_score(from);
+        self.score_log.push(ScoreAdjustment {
+            pid,
+            name: entry.player.name.clone(),
+            from,
+            to: entry.player.score,
+            reason: reason.to_string(),
+            at: PlayerEntry::time_ms(),
+        });
+        self.broadcast_state().await?;
+        self.broadcast_player_states().await
+    }
+
+    /// Drops a player from the room, closes their connection and cleans up after them.
+    async fn remove_player(
+        &mut self,
+        pid: PlayerId,
+        reason: &str,
+        banned: bool,
+    ) -> anyhow::Result<()> {
+        let Some(idx) = self.players.iter().position(|p| p.player.pid == pid) else {
+            return Ok(());
+        };
+        let entry = self.players.remove(idx);
+        let reason = if reason.is_empty() {
+            "Removed by the host".to_string()
+        } else {
+            reason.to_string()
+        };
+        if !entry.sender.is_full() {
+            let _ = entry.sender.send(WsMsg::Kicked { reason, banned }).await;
+        }
+        entry.sender.close();
+
+        self.pending_buzzes.retain(|b| b.pid != pid);
+        self.final_responses.remove(&pid);
+        let was_revealing = self.final_order.first() == Some(&pid);
+        self.final_order.retain(|p| *p != pid);
+        if self.board_control == Some(pid) {
+            self.board_control = None;
+        }
+        if self.current_buzzer == Some(pid) {
+            if self.current_wager.is_some() || self.state == GameState::Wager {
+                // Their Daily Double, nobody else may answer it
+                self.finish_question();
+            } else if self.state == GameState::Answer {
+                self.current_buzzer = None;
+                if self.players.iter().any(|p| !p.player.buzzed) {
+                    self.set_state(GameState::WaitingForBuzz);
+                } else {
+                    self.finish_question();
+                }
+            }
+        }
+        if self.state == GameState::FinalReveal && self.final_order.is_empty() {
+            self.set_state(GameState::GameEnd);
+        }
+
+        if let Some(host) = &self.host {
+            host.sender.send(self.player_list_msg()).await?;
+        }
+        self.broadcast_state().await?;
+        self.broadcast_player_states().await?;
+        if was_revealing {
+            self.reveal_next_final().await?;
+        }
+        Ok(())
+    }
+
+    pub fn player_list_msg(&self) -> WsMsg {
+        WsMsg::PlayerList(self.players.iter().map(|e| e.player.clone()).collect())
+    }
+
+    /// Starts a heartbeat for every player, forgetting ones that were never answered.
+    /// Send the batch after releasing the room lock.
+    pub fn heartbeat_all(&mut self) -> HeartbeatBatch {
+        let now = PlayerEntry::time_ms();
+        let mut sends = Vec::with_capacity(self.players.len());
+        for entry in &mut self.players {
+            let expired = entry.expire_heartbeats(now);
+            if expired > 0 {
+                println!(
+                    "player {} never answered {expired} heartbeat(s), dropped them",
+                    entry.player.pid
+                );
+            }
+            sends.push((entry.sender.clone(), entry.start_heartbeat()));
+        }
+        HeartbeatBatch { sends }
+    }
+
+    /// Heartbeats the ticker should send now, if the next automatic round is due.
+    /// Rounds only run while a player is connected, so empty rooms stay quiet.
+    pub fn due_heartbeats(&mut self) -> Option<HeartbeatBatch> {
+        let connected = self
+            .players
+            .iter()
+            .any(|p| p.player.status == ConnectionStatus::Connected);
+        if !connected {
+            self.next_heartbeat = None;
+            return None;
+        }
+        let delay = self.settings.next_heartbeat_delay()?;
+        match self.next_heartbeat {
+            Some(at) if at <= Instant::now() => {
+                self.next_heartbeat = Some(Instant::now() + delay);
+                Some(self.heartbeat_all())
+            }
+            Some(_) => None,
+            None => {
+                self.next_heartbeat = Some(Instant::now() + delay);
+                None
+            }
+        }
+    }
+
+    /// Gives the buzz to a player and moves to the answer state.
+    async fn award_buzz(&mut self, player_id: PlayerId) -> anyhow::Result<()> {
+        let Some(player_entry) = self.players.iter_mut().find(|p| p.player.pid == player_id) else {
+            return Ok(());
+        };
+        player_entry.player.buzzed = true;
+        let buzzed_msg = WsMsg::Buzzed {
+            pid: player_id,
+            name: player_entry.player.name.clone(),
+        };
+        self.current_buzzer = Some(player_id);
+        self.set_state(GameState::Answer);
+
+        if let Some(host) = &self.host {
+            host.sender.send(buzzed_msg).await?;
+        }
+
+        self.broadcast_state().await?;
+        self.broadcast_player_states().await
+    }
+
+    /// Closes the arbitration window: the earliest latency-adjusted buzz wins, and the host
+    /// gets the whole ranking.
+    async fn resolve_buzzes(&mut self) -> anyhow::Result<()> {
+        self.arbitration_deadline = None;
+        let mut ranking = std::mem::take(&mut self.pending_buzzes);
+        ranking.sort_by_key(|b| (b.adjusted_at, b.received_at));
+        let winner = ranking.first().map(|b| b.pid);
+        if let Some(host) = &self.host {
+            host.sender.send(WsMsg::BuzzRanking { ranking }).await?;
+        }
+        if let Some(winner) = winner {
+            self.award_buzz(winner).await?;
+        }
+        Ok(())
+    }
+
+    /// Turns this question's early buzzes into lockouts, starting now that buzzing opens.
+    fn lock_out_early_buzzers(&mut self, now: UnixMs) {
+        let Some(lockout_ms) = self.settings.early_buzz_lockout_ms else {
+            return;
+        };
+        for player in &mut self.players {
+            if player.buzzed_early {
+                player.buzzed_early = false;
+                player.locked_until = Some(now + lockout_ms);
+                self.wake.notify_one();
+            }
+        }
+    }
+
+    /// Marks the room as in use, which keeps it from expiring.
+    pub fn touch(&mut self) {
+        self.last_activity = Instant::now();
+    }
+
+    /// When `tick` next has work to do, None while no timer, buzz window or lockout is running.
+    pub fn next_tick(&self) -> Option<Instant> {
+        let now = Instant::now();
+        let now_ms = PlayerEntry::time_ms();
+        let at_ms = |ms: UnixMs| now + Duration::from_millis(ms.saturating_sub(now_ms));
+        let timer = self.timer.as_ref().map(|timer| {
+            let seconds_left = timer.seconds_left();
+            if seconds_left == 0 || timer.last_tick != Some(seconds_left) {
+                return now;
+            }
+            // When the countdown drops to the next whole second
+            timer
+                .deadline
+                .checked_sub(Duration::from_secs((seconds_left - 1).into()))
+                .unwrap_or(now)
+        });
+        let arbitration = self.arbitration_deadline.map(at_ms);
+        let lockout = self
+            .players
             .iter()
-            .any(|cat| cat.questions.iter().any(|q| !q.answered))
+            .filter_map(|p| p.locked_until)
+            .min()
+            .map(at_ms);
+        [timer, arbitration, lockout, self.next_heartbeat]
+            .into_iter()
+            .flatten()
+            .min()
+    }
+
+    /// Called by the room's ticker task: sends countdown ticks and handles expired timers.
+    pub async fn tick(&mut self) -> anyhow::Result<()> {
+        let now = PlayerEntry::time_ms();
+        let mut lockout_ended = false;
+        for player in &mut self.players {
+            if player.locked_until.is_some() && !player.is_locked_out(now) {
+                player.locked_until = None;
+                lockout_ended = true;
+            }
+        }
+        if lockout_ended {
+            self.broadcast_player_states().await?;
+        }
+
+        if self
+            .arbitration_deadline
+            .is_some_and(|deadline| now >= deadline)
+        {
+            self.resolve_buzzes().await?;
+        }
+
+        let Some(timer) = &mut self.timer else {
+            return Ok(());
+        };
+        let seconds_left = timer.seconds_left();
+        if seconds_left == 0 {
+            let kind = timer.kind;
+            self.timer = None;
+            match kind {
+                TimerKind::Answer => {
+                    println!("answer timer expired in room {}", self.code);
+                    self.judge_answer(false).await?;
+                }
+                TimerKind::Buzz if !self.pending_buzzes.is_empty() => {
+                    self.resolve_buzzes().await?;
+                }
+                TimerKind::Buzz => {
+                    println!("buzz window closed in room {}", self.code);
+                    self.close_unanswered().await?;
+                }
+                TimerKind::Reveal => {
+                    self.finish_question();
+                    self.broadcast_state().await?;
+                    self.broadcast_player_states().await?;
+                }
+            }
+        } else if timer.last_tick != Some(seconds_left) {
+            timer.last_tick = Some(seconds_left);
+            let msg = WsMsg::TimerTick {
+                kind: timer.kind,
+                seconds_left,
+            };
+            self.broadcast(msg).await?;
+        }
+        Ok(())
+    }
+
+    /// Changes state and (re)starts or cancels the timer that goes with it.
+    fn set_state(&mut self, state: GameState) {
+        self.state = state;
+        if self.state != GameState::WaitingForBuzz {
+            self.pending_buzzes.clear();
+            self.arbitration_deadline = None;
+        }
+        if self.state != GameState::Reveal {
+            self.reveal = None;
+        }
+        self.timer = match self.state {
+            GameState::Answer => self
+                .answer_seconds()
+                .map(|seconds| RoomTimer::new(TimerKind::Answer, seconds)),
+            GameState::WaitingForBuzz => self
+                .settings
+                .buzz_seconds
+                .map(|seconds| RoomTimer::new(TimerKind::Buzz, seconds)),
+            GameState::Reveal => self
+                .settings
+                .reveal_seconds
+                .map(|seconds| RoomTimer::new(TimerKind::Reveal, seconds)),
+            _ => None,
+        };
+        self.wake.notify_one();
+    }
+
+    /// Nobody buzzed in time: shows everyone the answer, the clue closes unscored.
+    async fn close_unanswered(&mut self) -> anyhow::Result<()> {
+        self.start_reveal(None).await?;
+        self.broadcast_state().await?;
+        self.broadcast_player_states().await?;
+        Ok(())
+    }
+
+    /// Shows everyone the answer to the current clue, which closes once the reveal ends.
+    async fn start_reveal(&mut self, winner: Option<PlayerId>) -> anyhow::Result<()> {
+        let answer = self
+            .current_question
+            .and_then(|(cat_idx, q_idx)| self.question_at(cat_idx, q_idx))
+            .map(|q| q.answer.clone())
+            .unwrap_or_default();
+        let name = winner.and_then(|pid| {
+            self.players
+                .iter()
+                .find(|p| p.player.pid == pid)
+                .map(|p| p.player.name.clone())
+        });
+        self.current_buzzer = None;
+        self.set_state(GameState::Reveal);
+        self.reveal = Some(Reveal {
+            answer: answer.clone(),
+            pid: winner,
+            name,
+        });
+        self.broadcast(WsMsg::AnswerRevealed {
+            answer,
+            pid: winner,
+        })
+        .await
+    }
+
+    fn answer_seconds(&self) -> Option<u32> {
+        self.current_question
+            .and_then(|(cat_idx, q_idx)| self.question_at(cat_idx, q_idx))
+            .and_then(|q| q.answer_seconds)
+            .or(self.settings.answer_seconds)
+    }
+
+    /// Marks the current question answered and moves on to the next selection (or game end).
+    fn finish_question(&mut self) {
+        if let Some((cat_idx, q_idx)) = self.current_question
+            && let Some(round) = self.rounds.get_mut(self.current_round)
+            && let Some(category) = round.categories.get_mut(cat_idx)
+            && let Some(question) = category.questions.get_mut(q_idx)
+        {
+            question.answered = true;
+        }
+        self.current_question = None;
+        self.current_buzzer = None;
+        self.current_wager = None;
+
+        if self.has_remaining_questions() || self.advance_round() {
+            self.set_state(GameState::Selection);
+        } else {
+            self.start_final_round();
+        }
+    }
+
+    /// Moves on to the next round that still has questions on its board, if there is one.
+    fn advance_round(&mut self) -> bool {
+        let next = self
+            .rounds
+            .iter()
+            .enumerate()
+            .skip(self.current_round + 1)
+            .find(|(_, round)| {
+                round
+                    .categories
+                    .iter()
+                    .any(|cat| cat.questions.iter().any(|q| !q.answered))
+            })
+            .map(|(idx, _)| idx);
+        if let Some(idx) = next {
+            self.current_round = idx;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Enters Final Jeopardy with every positive-score player, or ends the game if there is
+    /// no final question or nobody is eligible.
+    fn start_final_round(&mut self) {
+        let mut eligible: Vec<&PlayerEntry> =
+            self.players.iter().filter(|p| p.player.score > 0).collect();
+        if self.final_question.is_none() || eligible.is_empty() {
+            self.set_state(GameState::GameEnd);
+            return;
+        }
+        eligible.sort_by_key(|p| p.player.score);
+        self.final_order = eligible.iter().map(|p| p.player.pid).collect();
+        self.final_responses = self
+            .final_order
+            .iter()
+            .map(|pid| (*pid, FinalResponse::default()))
+            .collect();
+        self.set_state(GameState::FinalCategory);
+    }
+
+    /// Broadcasts the sealed response of the next player to be graded, if any.
+    async fn reveal_next_final(&self) -> anyhow::Result<()> {
+        let Some(player_id) = self.final_order.first() else {
+            return Ok(());
+        };
+        let Some(entry) = self.players.iter().find(|p| p.player.pid == *player_id) else {
+            return Ok(());
+        };
+        let response = self
+            .final_responses
+            .get(player_id)
+            .cloned()
+            .unwrap_or_default();
+        let msg = WsMsg::FinalReveal {
+            pid: *player_id,
+            name: entry.player.name.clone(),
+            answer: response.answer.unwrap_or_default(),
+            wager: response.wager.unwrap_or(0),
+        };
+        self.broadcast(msg).await
+    }
+
+    /// Lets the host know a sealed Final Jeopardy submission came in, without its contents.
+    async fn send_final_submitted(&self, pid: PlayerId) -> anyhow::Result<()> {
+        let msg = WsMsg::FinalSubmitted { pid };
+        if let Some(host) = &self.host {
+            host.sender.send(msg.clone()).await?;
+        }
+        self.send_to_player(pid, msg).await;
+        Ok(())
+    }
+
+    fn round(&self) -> Option<&Round> {
+        self.rounds.get(self.current_round)
+    }
+
+    fn question_at(&self, cat_idx: usize, q_idx: usize) -> Option<&Question> {
+        self.round()?.categories.get(cat_idx)?.questions.get(q_idx)
+    }
+
+    /// The current round's value of a question, multiplier included.
+    fn question_value(&self, cat_idx: usize, q_idx: usize) -> Option<u32> {
+        let round = self.round()?;
+        Some(round.value_of(self.question_at(cat_idx, q_idx)?))
+    }
+
+    /// The player who picks the next clue: whoever answered correctly last (or was handed
+    /// control by the host), falling back to the lowest score (earliest joined on ties).
+    fn selecting_player(&self) -> Option<PlayerId> {
+        if let Some(pid) = self.board_control
+            && self.players.iter().any(|p| p.player.pid == pid)
+        {
+            return Some(pid);
+        }
+        self.players
+            .iter()
+            .min_by_key(|p| p.player.score)
+            .map(|p| p.player.pid)
+    }
+
+    /// Jeopardy wager limit: the player's score or the board's top value, whichever is higher.
+    fn max_wager(&self, pid: PlayerId) -> u32 {
+        let score = self
+            .players
+            .iter()
+            .find(|p| p.player.pid == pid)
+            .and_then(|p| u32::try_from(p.player.score).ok())
+            .unwrap_or(0);
+        let top_value = self
+            .round()
+            .and_then(|round| {
+                round
+                    .categories
+                    .iter()
+                    .flat_map(|c| c.questions.iter())
+                    .map(|q| round.value_of(q))
+                    .max()
+            })
+            .unwrap_or(0);
+        score.max(top_value)
+    }
+
+    async fn send_to_player(&self, pid: PlayerId, msg: WsMsg) {
+        if let Some(entry) = self.players.iter().find(|p| p.player.pid == pid) {
+            let _ = entry.sender.send(msg).await;
+        }
+    }
+
+    async fn send_to(&self, role: Role, msg: WsMsg) {
+        let sender = match role {
+            Role::Host => self.host.as_ref().map(|h| &h.sender),
+            Role::Player(pid) => return self.send_to_player(pid, msg).await,
+            Role::Display(id) => self.displays.iter().find(|d| d.id == id).map(|d| &d.sender),
+            Role::Pending(id) => self
+                .pending
+                .iter()
+                .find(|p| p.pending.id == id)
+                .map(|p| &p.sender),
+        };
+        if let Some(sender) = sender {
+            let _ = sender.send(msg).await;
+        }
+    }
+
+    fn has_remaining_questions(&self) -> bool {
+        self.round().is_some_and(|round| {
+            round
+                .categories
+                .iter()
+                .any(|cat| cat.questions.iter().any(|q| !q.answered))
+        })
     }
 }
 
@@ -273,8 +1783,1175 @@ pub enum GameState {
     #[default]
     Start,
     Selection,
+    Wager,
     QuestionReading,
     Answer,
     WaitingForBuzz,
+    Reveal, // the answer to the clue that just closed is shown
+    FinalCategory,
+    FinalClue,
+    FinalReveal,
     GameEnd,
 }
+
+/// Tells a connection why it is being dropped and closes its channel, which ends its session.
+pub async fn end_session(sender: &Sender<WsMsg>, reason: ErrorCode) {
+    if !sender.is_closed() && !sender.is_full() {
+        let _ = sender.send(WsMsg::error(reason)).await;
+    }
+    sender.close();
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio_mpmc::{Receiver, channel};
+
+    use super::*;
+    use crate::ws_msg::tests::samples;
+
+    struct TestRoom {
+        room: Room,
+        host_rx: Receiver<WsMsg>,
+        player_rx: Receiver<WsMsg>,
+        display_rx: Receiver<WsMsg>,
+        display: DisplayId,
+        pending_rx: Receiver<WsMsg>,
+        pending: PendingId,
+    }
+
+    fn test_room() -> TestRoom {
+        let mut room = Room::new(
+            "ABCDEF".to_string(),
+            "host".to_string(),
+            "display".to_string(),
+        );
+        room.rounds = vec![Round::new(vec![Category {
+            title: "Rivers".to_string(),
+            questions: vec![Question {
+                text: "Longest river in Africa".to_string(),
+                answer: "The Nile".to_string(),
+                value: 200,
+                answered: false,
+                answer_seconds: None,
+                daily_double: false,
+            }],
+        }])];
+        room.settings.heartbeat_interval_ms = 0;
+
+        let (host_tx, host_rx) = channel(64);
+        room.host = Some(HostEntry::new(0, host_tx));
+        let (player_tx, player_rx) = channel(64);
+        let pid = room.next_player_id();
+        room.players.push(PlayerEntry::new(
+            Player::new(pid, "alice".to_string()),
+            "s3cret-token".to_string(),
+            player_tx,
+        ));
+        let (display_tx, display_rx) = channel(64);
+        let display = room.attach_display(display_tx);
+        let (pending_tx, pending_rx) = channel(64);
+        let pending = room.add_pending("bob".to_string(), pending_tx, [127, 0, 0, 1].into());
+        TestRoom {
+            room,
+            host_rx,
+            player_rx,
+            display_rx,
+            display,
+            pending_rx,
+            pending,
+        }
+    }
+
+    async fn drain(rx: &Receiver<WsMsg>) -> Vec<WsMsg> {
+        let mut msgs = Vec::new();
+        while !rx.is_empty() {
+            if let Ok(Some(msg)) = rx.recv().await {
+                msgs.push(msg);
+            }
+        }
+        msgs
+    }
+
+    fn error_code(msgs: &[WsMsg]) -> Option<ErrorCode> {
+        match msgs {
+            [WsMsg::Error { code, .. }] => Some(*code),
+            _ => None,
+        }
+    }
+
+    #[tokio::test]
+    async fn only_the_wagering_player_answers_a_daily_double() {
+        for correct in [true, false] {
+            let mut t = test_room();
+            t.room.rounds[0].categories[0].questions[0].daily_double = true;
+            t.room.players[0].player.score = 100;
+            let (carol_tx, carol_rx) = channel(64);
+            let carol = t.room.next_player_id();
+            t.room.players.push(PlayerEntry::new(
+                Player::new(carol, "carol".to_string()),
+                "carol-token".to_string(),
+                carol_tx,
+            ));
+            t.room.board_control = Some(1);
+            t.room.state = GameState::Selection;
+            let pick = WsMsg::HostChoice {
+                category_index: 0,
+                question_index: 0,
+            };
+            t.room.update(&pick, Role::Host).await.expect("update");
+            assert_eq!(t.room.state, GameState::Wager);
+            assert!(drain(&t.player_rx).await.iter().any(|m| matches!(
+                m,
+                WsMsg::DailyDouble {
+                    pid: 1,
+                    min_wager: MIN_DAILY_DOUBLE_WAGER,
+                    max_wager: 200,
+                }
+            )));
+
+            drain(&carol_rx).await;
+            drain(&t.host_rx).await;
+
+            // Not their Daily Double
+            let wager = WsMsg::Wager { amount: 100 };
+            t.room
+                .update(&wager, Role::Player(carol))
+                .await
+                .expect("update");
+            assert!(matches!(
+                drain(&carol_rx).await.as_slice(),
+                [WsMsg::WagerRejected { max_wager: 0, .. }]
+            ));
+            // Below the minimum, then above the board's top value
+            for amount in [MIN_DAILY_DOUBLE_WAGER - 1, 201] {
+                let wager = WsMsg::Wager { amount };
+                t.room
+                    .update(&wager, Role::Player(1))
+                    .await
+                    .expect("update");
+                assert!(matches!(
+                    drain(&t.player_rx).await.as_slice(),
+                    [WsMsg::WagerRejected {
+                        min_wager: MIN_DAILY_DOUBLE_WAGER,
+                        max_wager: 200,
+                    }]
+                ));
+            }
+            // Ready before the wager is in changes nothing
+            let ready = WsMsg::HostReady {};
+            t.room.update(&ready, Role::Host).await.expect("update");
+            assert_eq!(
+                error_code(&drain(&t.host_rx).await),
+                Some(ErrorCode::WrongState)
+            );
+            assert_eq!(t.room.state, GameState::Wager);
+
+            let wager = WsMsg::Wager { amount: 150 };
+            t.room
+                .update(&wager, Role::Player(1))
+                .await
+                .expect("update");
+            assert_eq!(t.room.state, GameState::QuestionReading);
+            assert_eq!(t.room.current_wager, Some(150));
+            t.room.update(&ready, Role::Host).await.expect("update");
+            assert_eq!(t.room.state, GameState::Answer);
+            assert_eq!(t.room.current_buzzer, Some(1));
+
+            let buzz = WsMsg::Buzz { t_pressed: None };
+            t.room
+                .update(&buzz, Role::Player(carol))
+                .await
+                .expect("update");
+            assert_eq!(t.room.current_buzzer, Some(1));
+            let ruling = WsMsg::HostChecked { correct };
+            t.room.update(&ruling, Role::Host).await.expect("update");
+            assert_eq!(t.room.state, GameState::Reveal);
+            let expected = if correct { 250 } else { -50 };
+            assert_eq!(t.room.players[0].player.score, expected);
+            assert_eq!(t.room.players[1].player.score, 0);
+        }
+    }
+
+    #[tokio::test]
+    async fn host_ready_only_moves_on_where_there_is_something_to_move_on_from() {
+        for state in [
+            GameState::Start,
+            GameState::Selection,
+            GameState::Wager,
+            GameState::WaitingForBuzz,
+            GameState::Answer,
+            GameState::FinalReveal,
+            GameState::GameEnd,
+        ] {
+            let mut t = test_room();
+            t.room.state = state.clone();
+            t.room.current_question = Some((0, 0));
+            t.room.current_buzzer = Some(1);
+            t.room
+                .update(&WsMsg::HostReady {}, Role::Host)
+                .await
+                .expect("update");
+            assert_eq!(
+                error_code(&drain(&t.host_rx).await),
+                Some(ErrorCode::WrongState),
+                "{state:?}"
+            );
+            assert_eq!(t.room.state, state);
+            assert_eq!(t.room.current_question, Some((0, 0)), "{state:?}");
+            assert_eq!(t.room.current_buzzer, Some(1), "{state:?}");
+            assert!(drain(&t.player_rx).await.is_empty(), "{state:?}");
+        }
+    }
+
+    #[tokio::test]
+    async fn final_jeopardy_moves_on_with_host_ready() {
+        let mut t = test_room();
+        t.room.final_question = Some(FinalQuestion {
+            category: "Capitals".to_string(),
+            text: "Capital of Australia".to_string(),
+            answer: "Canberra".to_string(),
+        });
+        t.room.players[0].player.score = 200;
+        t.room.state = GameState::Reveal;
+        t.room.current_question = Some((0, 0));
+        let ready = WsMsg::HostReady {};
+        t.room.update(&ready, Role::Host).await.expect("update");
+        assert_eq!(t.room.state, GameState::FinalCategory);
+
+        let wager = WsMsg::FinalWager { amount: 100 };
+        t.room
+            .update(&wager, Role::Player(1))
+            .await
+            .expect("update");
+        t.room.update(&ready, Role::Host).await.expect("update");
+        assert_eq!(t.room.state, GameState::FinalClue);
+        let answer = WsMsg::FinalAnswer {
+            answer: "What is Canberra?".to_string(),
+        };
+        t.room
+            .update(&answer, Role::Player(1))
+            .await
+            .expect("update");
+        t.room.update(&ready, Role::Host).await.expect("update");
+        assert_eq!(t.room.state, GameState::FinalReveal);
+        drain(&t.host_rx).await;
+
+        // Grading is done with HostChecked, HostReady must not skip ahead
+        t.room.update(&ready, Role::Host).await.expect("update");
+        assert_eq!(
+            error_code(&drain(&t.host_rx).await),
+            Some(ErrorCode::WrongState)
+        );
+        assert_eq!(t.room.state, GameState::FinalReveal);
+        let correct = WsMsg::HostChecked { correct: true };
+        t.room.update(&correct, Role::Host).await.expect("update");
+        assert_eq!(t.room.state, GameState::GameEnd);
+        assert_eq!(t.room.players[0].player.score, 300);
+    }
+
+    #[tokio::test]
+    async fn later_rounds_skip_cleared_boards_and_scale_values() {
+        let mut t = test_room();
+        let mut cleared = t.room.rounds[0].clone();
+        cleared.categories[0].questions[0].answered = true;
+        let mut double = t.room.rounds[0].clone();
+        double.multiplier = Some(2);
+        t.room.rounds.extend([cleared, double]);
+        let ready = WsMsg::HostReady {};
+        let correct = WsMsg::HostChecked { correct: true };
+
+        t.room.state = GameState::Answer;
+        t.room.current_question = Some((0, 0));
+        t.room.current_buzzer = Some(1);
+        t.room.update(&correct, Role::Host).await.expect("update");
+        t.room.update(&ready, Role::Host).await.expect("update");
+        assert_eq!(t.room.state, GameState::Selection);
+        assert_eq!(t.room.current_round, 2, "the cleared round is skipped");
+        assert_eq!(t.room.players[0].player.score, 200);
+
+        let board = t.room.game_state_msg(Audience::Player);
+        assert!(matches!(
+            board,
+            WsMsg::GameState { round: 2, categories, .. }
+            if categories[0].questions[0].value == 400
+        ));
+        assert_eq!(t.room.rounds[2].categories[0].questions[0].value, 200);
+
+        let pick = WsMsg::HostChoice {
+            category_index: 0,
+            question_index: 0,
+        };
+        t.room.update(&pick, Role::Host).await.expect("update");
+        t.room.update(&ready, Role::Host).await.expect("update");
+        t.room
+            .update(&WsMsg::Buzz { t_pressed: None }, Role::Player(1))
+            .await
+            .expect("update");
+        t.room.update(&correct, Role::Host).await.expect("update");
+        assert_eq!(t.room.players[0].player.score, 600);
+    }
+
+    #[tokio::test]
+    async fn an_answer_timeout_counts_as_wrong() {
+        let mut t = test_room();
+        let (carol_tx, _carol_rx) = channel(64);
+        let carol = t.room.next_player_id();
+        t.room.players.push(PlayerEntry::new(
+            Player::new(carol, "carol".to_string()),
+            "carol-token".to_string(),
+            carol_tx,
+        ));
+        t.room.settings.answer_seconds = Some(3);
+        t.room.current_question = Some((0, 0));
+        t.room.set_state(GameState::WaitingForBuzz);
+        t.room
+            .update(&WsMsg::Buzz { t_pressed: None }, Role::Player(1))
+            .await
+            .expect("update");
+        assert_eq!(t.room.state, GameState::Answer);
+        let timer = t.room.timer.as_mut().expect("answer timer");
+        assert_eq!(timer.kind, TimerKind::Answer);
+        timer.deadline = Instant::now();
+
+        t.room.tick().await.expect("tick");
+        assert_eq!(t.room.players[0].player.score, -200);
+        assert_eq!(
+            t.room.state,
+            GameState::WaitingForBuzz,
+            "carol may still buzz"
+        );
+        assert_eq!(t.room.current_buzzer, None);
+        assert_eq!(t.room.history.len(), 1, "undoable like a ruling");
+    }
+
+    #[tokio::test]
+    async fn a_buzz_timeout_reveals_the_answer_unscored() {
+        let mut t = test_room();
+        t.room.settings.buzz_seconds = Some(5);
+        t.room.current_question = Some((0, 0));
+        t.room.state = GameState::QuestionReading;
+        t.room
+            .update(&WsMsg::HostReady {}, Role::Host)
+            .await
+            .expect("update");
+        let timer = t.room.timer.as_mut().expect("buzz timer");
+        assert_eq!(timer.kind, TimerKind::Buzz);
+        timer.deadline = Instant::now();
+        drain(&t.player_rx).await;
+
+        t.room.tick().await.expect("tick");
+        assert_eq!(t.room.state, GameState::Reveal);
+        assert_eq!(t.room.players[0].player.score, 0);
+        assert!(matches!(
+            drain(&t.player_rx).await.first(),
+            Some(WsMsg::AnswerRevealed { pid: None, .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn an_early_buzz_locks_the_player_out_for_a_while() {
+        let mut t = test_room();
+        t.room.settings.early_buzz_lockout_ms = Some(250);
+        t.room.current_question = Some((0, 0));
+        t.room.state = GameState::QuestionReading;
+        let buzz = WsMsg::Buzz { t_pressed: None };
+        t.room.update(&buzz, Role::Player(1)).await.expect("update");
+        assert!(t.room.players[0].buzzed_early);
+        t.room
+            .update(&WsMsg::HostReady {}, Role::Host)
+            .await
+            .expect("update");
+        assert_eq!(t.room.state, GameState::WaitingForBuzz);
+        assert!(t.room.players[0].locked_until.is_some());
+
+        t.room.update(&buzz, Role::Player(1)).await.expect("update");
+        assert_eq!(t.room.current_buzzer, None, "locked out");
+        assert_eq!(t.room.state, GameState::WaitingForBuzz);
+        drain(&t.player_rx).await;
+
+        t.room.players[0].locked_until = Some(PlayerEntry::time_ms() - 1);
+        t.room.tick().await.expect("tick");
+        assert_eq!(t.room.players[0].locked_until, None);
+        assert!(matches!(
+            drain(&t.player_rx).await.as_slice(),
+            [WsMsg::PlayerState {
+                locked_out: false,
+                can_buzz: true,
+                ..
+            }]
+        ));
+        t.room.update(&buzz, Role::Player(1)).await.expect("update");
+        assert_eq!(t.room.current_buzzer, Some(1));
+    }
+
+    #[tokio::test]
+    async fn the_earliest_adjusted_buzz_wins_the_arbitration() {
+        let mut t = test_room();
+        t.room.settings.buzz_arbitration_ms = Some(100);
+        // Alice's round trip is 300 ms, carol's 20 ms, dave has no samples yet
+        for (name, rtt) in [("carol", 20), ("dave", 0)] {
+            let (tx, _rx) = channel(64);
+            let pid = t.room.next_player_id();
+            t.room.players.push(PlayerEntry::new(
+                Player::new(pid, name.to_string()),
+                format!("{name}-token"),
+                tx,
+            ));
+            if rtt > 0 {
+                let WsMsg::DoHeartbeat { hbid, t_sent } = t.room.players[1].start_heartbeat()
+                else {
+                    panic!("expected a DoHeartbeat");
+                };
+                assert!(t.room.players[1].on_heartbeat(hbid, t_sent, t_sent, t_sent + rtt));
+            }
+        }
+        let WsMsg::DoHeartbeat { hbid, t_sent } = t.room.players[0].start_heartbeat() else {
+            panic!("expected a DoHeartbeat");
+        };
+        assert!(t.room.players[0].on_heartbeat(hbid, t_sent, t_sent, t_sent + 300));
+        t.room.current_question = Some((0, 0));
+        t.room.state = GameState::WaitingForBuzz;
+
+        // Alice's buzz arrives last but was pressed first
+        let buzz = WsMsg::Buzz { t_pressed: None };
+        for pid in [3, 2, 1] {
+            t.room
+                .update(&buzz, Role::Player(pid))
+                .await
+                .expect("update");
+        }
+        assert_eq!(t.room.current_buzzer, None, "the window is still open");
+        assert_eq!(t.room.pending_buzzes.len(), 3);
+        drain(&t.host_rx).await;
+
+        t.room.arbitration_deadline = Some(PlayerEntry::time_ms() - 1);
+        t.room.tick().await.expect("tick");
+        assert_eq!(t.room.current_buzzer, Some(1));
+        assert_eq!(t.room.state, GameState::Answer);
+        let host_msgs = drain(&t.host_rx).await;
+        let [
+            WsMsg::BuzzRanking { ranking },
+            WsMsg::Buzzed { pid: 1, .. },
+            ..,
+        ] = host_msgs.as_slice()
+        else {
+            panic!("expected the ranking, then the winner: {host_msgs:?}");
+        };
+        let order: Vec<PlayerId> = ranking.iter().map(|b| b.pid).collect();
+        assert_eq!(order, [1, 2, 3]);
+        assert_eq!(ranking[0].latency, 150);
+        assert_eq!(ranking[1].latency, 10);
+        assert_eq!(ranking[2].latency, 0);
+    }
+
+    #[tokio::test]
+    async fn unauthorized_messages_are_refused_with_an_error() {
+        for (msg, origin) in samples() {
+            for which in 0..4 {
+                let mut t = test_room();
+                let role = match which {
+                    0 => Role::Host,
+                    1 => Role::Player(1),
+                    2 => Role::Display(t.display),
+                    _ => Role::Pending(t.pending),
+                };
+                let code = match (origin, role) {
+                    (Origin::Host, Role::Host) | (Origin::Player, Role::Player(_)) => continue,
+                    (Origin::Host, _) => ErrorCode::NotHost,
+                    (Origin::Player, _) => ErrorCode::NotPlayer,
+                    (Origin::Server, _) => ErrorCode::ServerOnly,
+                };
+                t.room.update(&msg, role).await.expect("update");
+
+                assert_eq!(t.room.state, GameState::Start, "{role:?} {msg:?}");
+                let (host, player, display, pending) = (
+                    drain(&t.host_rx).await,
+                    drain(&t.player_rx).await,
+                    drain(&t.display_rx).await,
+                    drain(&t.pending_rx).await,
+                );
+                let (own, others) = match role {
+                    Role::Host => (host, [player, display, pending]),
+                    Role::Player(_) => (player, [host, display, pending]),
+                    Role::Display(_) => (display, [host, player, pending]),
+                    Role::Pending(_) => (pending, [host, player, display]),
+                };
+                assert_eq!(error_code(&own), Some(code), "{role:?} {msg:?}");
+                assert!(others.iter().all(Vec::is_empty), "{role:?} {msg:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn game_state_never_carries_tokens() {
+        let t = test_room();
+        for audience in [Audience::Host, Audience::Player, Audience::Display] {
+            let json = serde_json::to_string(&t.room.game_state_msg(audience)).expect("serialize");
+            assert!(!json.contains("s3cret-token"), "{audience:?}: {json}");
+            assert!(
+                json.contains("\"status\":\"connected\""),
+                "{audience:?}: {json}"
+            );
+        }
+    }
+
+    #[test]
+    fn final_clue_stays_hidden_from_players_until_the_reveal() {
+        let mut t = test_room();
+        t.room.final_question = Some(FinalQuestion {
+            category: "Capitals".to_string(),
+            text: "Capital of Australia".to_string(),
+            answer: "Canberra".to_string(),
+        });
+        let visible = |room: &Room, audience| match room.game_state_msg(audience) {
+            WsMsg::GameState { final_question, .. } => final_question.map(|q| (q.text, q.answer)),
+            _ => None,
+        };
+        for state in [
+            GameState::Selection,
+            GameState::FinalCategory,
+            GameState::FinalClue,
+            GameState::FinalReveal,
+            GameState::GameEnd,
+        ] {
+            t.room.state = state.clone();
+            let host = visible(&t.room, Audience::Host);
+            assert_eq!(
+                host,
+                Some(("Capital of Australia".to_string(), "Canberra".to_string()))
+            );
+            for audience in [Audience::Player, Audience::Display] {
+                let expected = match state {
+                    GameState::FinalCategory => Some((String::new(), String::new())),
+                    GameState::FinalClue => {
+                        Some(("Capital of Australia".to_string(), String::new()))
+                    }
+                    GameState::FinalReveal | GameState::GameEnd => host.clone(),
+                    _ => None,
+                };
+                assert_eq!(
+                    visible(&t.room, audience),
+                    expected,
+                    "{state:?} {audience:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn players_only_see_the_clue_being_read() {
+        let mut t = test_room();
+        t.room.rounds[0].categories[0].questions.push(Question {
+            text: "River through Baghdad".to_string(),
+            answer: "The Tigris".to_string(),
+            value: 400,
+            answered: false,
+            answer_seconds: None,
+            daily_double: false,
+        });
+        let texts = |room: &Room, audience| match room.game_state_msg(audience) {
+            WsMsg::GameState { categories, .. } => categories[0]
+                .questions
+                .iter()
+                .map(|q| (q.text.clone(), q.answer.clone()))
+                .collect(),
+            _ => Vec::new(),
+        };
+        let hidden = (String::new(), String::new());
+
+        t.room.state = GameState::Selection;
+        for audience in [Audience::Player, Audience::Display] {
+            assert_eq!(
+                texts(&t.room, audience),
+                [hidden.clone(), hidden.clone()],
+                "{audience:?}"
+            );
+        }
+        t.room.state = GameState::QuestionReading;
+        t.room.current_question = Some((0, 1));
+        for audience in [Audience::Player, Audience::Display] {
+            assert_eq!(
+                texts(&t.room, audience),
+                [
+                    hidden.clone(),
+                    ("River through Baghdad".to_string(), String::new())
+                ],
+                "{audience:?}"
+            );
+        }
+        assert_eq!(
+            texts(&t.room, Audience::Host)[0],
+            (
+                "Longest river in Africa".to_string(),
+                "The Nile".to_string()
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn host_commands_only_work_for_the_host() {
+        let mut t = test_room();
+        t.room
+            .update(&WsMsg::StartGame {}, Role::Player(1))
+            .await
+            .expect("update");
+        assert_eq!(t.room.state, GameState::Start);
+        assert_eq!(
+            error_code(&drain(&t.player_rx).await),
+            Some(ErrorCode::NotHost)
+        );
+
+        t.room
+            .update(&WsMsg::StartGame {}, Role::Host)
+            .await
+            .expect("update");
+        assert_eq!(t.room.state, GameState::Selection);
+    }
+
+    #[tokio::test]
+    async fn players_cannot_judge_their_own_answer() {
+        let mut t = test_room();
+        t.room.state = GameState::Answer;
+        t.room.current_question = Some((0, 0));
+        t.room.current_buzzer = Some(1);
+
+        t.room
+            .update(&WsMsg::HostChecked { correct: true }, Role::Player(1))
+            .await
+            .expect("update");
+        assert_eq!(t.room.state, GameState::Answer);
+        assert_eq!(t.room.players[0].player.score, 0);
+
+        t.room
+            .update(&WsMsg::HostChecked { correct: true }, Role::Host)
+            .await
+            .expect("update");
+        assert_eq!(t.room.players[0].player.score, 200);
+    }
+
+    #[tokio::test]
+    async fn kicking_the_answering_player_closes_their_clue() {
+        let mut t = test_room();
+        t.room.state = GameState::Answer;
+        t.room.current_question = Some((0, 0));
+        t.room.current_buzzer = Some(1);
+        t.room.players[0].player.buzzed = true;
+
+        let kick = WsMsg::KickPlayer {
+            pid: 1,
+            reason: String::new(),
+        };
+        t.room.update(&kick, Role::Host).await.expect("update");
+
+        assert!(t.room.players.is_empty());
+        assert_eq!(t.room.state, GameState::GameEnd);
+        assert!(matches!(
+            drain(&t.player_rx).await.as_slice(),
+            [WsMsg::Kicked { banned: false, .. }]
+        ));
+        assert!(t.player_rx.is_closed());
+        let host = drain(&t.host_rx).await;
+        assert!(matches!(host.first(), Some(WsMsg::PlayerList(list)) if list.is_empty()));
+    }
+
+    #[tokio::test]
+    async fn bans_block_token_and_optionally_name_and_ip() {
+        let ip: IpAddr = [10, 0, 0, 7].into();
+        let other_ip: IpAddr = [10, 0, 0, 8].into();
+        let mut t = test_room();
+        t.room.players[0].ip = Some(ip);
+        let ban = WsMsg::BanPlayer {
+            pid: 1,
+            reason: "trolling".to_string(),
+            by_name: true,
+            by_ip: false,
+        };
+        t.room.update(&ban, Role::Host).await.expect("update");
+
+        let bans = &t.room.bans;
+        assert!(bans.blocks(Some("s3cret-token"), None, other_ip));
+        assert!(bans.blocks(None, Some("ALICE"), other_ip));
+        assert!(!bans.blocks(None, Some("bob"), ip));
+        assert_eq!(t.room.next_player_id(), 2);
+    }
+
+    #[tokio::test]
+    async fn admitting_a_pending_join_creates_the_player() {
+        let mut t = test_room();
+        t.room
+            .update(&WsMsg::AdmitPlayer { id: t.pending }, Role::Host)
+            .await
+            .expect("update");
+
+        assert!(t.room.pending.is_empty());
+        assert_eq!(t.room.players.len(), 2);
+        assert!(matches!(
+            drain(&t.pending_rx).await.as_slice(),
+            [WsMsg::NewPlayer { pid: 2, .. }]
+        ));
+        let host = drain(&t.host_rx).await;
+        assert!(
+            matches!(host.as_slice(), [WsMsg::PlayerList(list), WsMsg::PendingList(pending)]
+            if list.len() == 2 && pending.is_empty())
+        );
+    }
+
+    #[tokio::test]
+    async fn rejecting_a_pending_join_closes_it() {
+        let mut t = test_room();
+        let reject = WsMsg::RejectPlayer {
+            id: t.pending,
+            reason: String::new(),
+        };
+        t.room.update(&reject, Role::Host).await.expect("update");
+
+        assert!(t.room.pending.is_empty());
+        assert_eq!(t.room.players.len(), 1);
+        assert!(matches!(
+            drain(&t.pending_rx).await.as_slice(),
+            [WsMsg::JoinRejected { .. }]
+        ));
+        assert!(t.pending_rx.is_closed());
+    }
+
+    #[test]
+    fn duplicate_names_are_suffixed_or_rejected() {
+        let mut t = test_room();
+        // "alice" is seated and "bob" is waiting for approval
+        assert_eq!(t.room.claim_name("ALICE"), Ok("ALICE 2".to_string()));
+        assert_eq!(t.room.claim_name("Bob"), Ok("Bob 2".to_string()));
+        assert_eq!(t.room.claim_name("carol"), Ok("carol".to_string()));
+        let long = "a".repeat(MAX_NAME_CHARS);
+        t.room.players[0].player.name = long.clone();
+        let suffixed = t.room.claim_name(&long).expect("suffixed");
+        assert_eq!(suffixed.chars().count(), MAX_NAME_CHARS);
+        assert!(suffixed.ends_with(" 2"));
+
+        t.room.settings.duplicate_names = DuplicateNames::Reject;
+        assert_eq!(t.room.claim_name("bob"), Err(ErrorCode::NameTaken));
+    }
+
+    #[tokio::test]
+    async fn player_commands_are_refused_from_the_host() {
+        let mut t = test_room();
+        t.room.state = GameState::WaitingForBuzz;
+        t.room.current_question = Some((0, 0));
+
+        t.room
+            .update(&WsMsg::Buzz { t_pressed: None }, Role::Host)
+            .await
+            .expect("update");
+        assert_eq!(t.room.state, GameState::WaitingForBuzz);
+        assert_eq!(
+            error_code(&drain(&t.host_rx).await),
+            Some(ErrorCode::NotPlayer)
+        );
+    }
+
+    #[tokio::test]
+    async fn manual_score_changes_are_logged_and_broadcast() {
+        let mut t = test_room();
+        let set = WsMsg::SetScore {
+            pid: 1,
+            score: 600,
+            reason: "misheard answer".to_string(),
+        };
+        t.room.update(&set, Role::Host).await.expect("update");
+        let adjust = WsMsg::AdjustScore {
+            pid: 1,
+            delta: -200,
+            reason: String::new(),
+        };
+        t.room.update(&adjust, Role::Host).await.expect("update");
+
+        assert_eq!(t.room.players[0].player.score, 400);
+        let log: Vec<(i32, i32)> = t.room.score_log.iter().map(|a| (a.from, a.to)).collect();
+        assert_eq!(log, [(0, 600), (600, 400)]);
+        assert_eq!(t.room.score_log[0].reason, "misheard answer");
+        assert!(matches!(
+            drain(&t.player_rx).await.as_slice(),
+            [.., WsMsg::PlayerState { score: 400, .. }]
+        ));
+
+        t.room
+            .update(&WsMsg::GetScoreLog {}, Role::Host)
+            .await
+            .expect("update");
+        assert!(matches!(
+            drain(&t.host_rx).await.as_slice(),
+            [.., WsMsg::ScoreLog { entries }] if entries.len() == 2
+        ));
+    }
+
+    #[tokio::test]
+    async fn undo_reverts_the_last_ruling() {
+        let mut t = test_room();
+        t.room
+            .update(&WsMsg::Undo {}, Role::Host)
+            .await
+            .expect("update");
+        assert_eq!(
+            error_code(&drain(&t.host_rx).await),
+            Some(ErrorCode::NothingToUndo)
+        );
+
+        t.room.state = GameState::Answer;
+        t.room.current_question = Some((0, 0));
+        t.room.current_buzzer = Some(1);
+        t.room.players[0].player.buzzed = true;
+        let correct = WsMsg::HostChecked { correct: true };
+        t.room.update(&correct, Role::Host).await.expect("update");
+        assert_eq!(t.room.players[0].player.score, 200);
+        assert_eq!(t.room.state, GameState::Reveal);
+        let bonus = WsMsg::AdjustScore {
+            pid: 1,
+            delta: 100,
+            reason: "style points".to_string(),
+        };
+        t.room.update(&bonus, Role::Host).await.expect("update");
+
+        drain(&t.player_rx).await;
+        t.room
+            .update(&WsMsg::Undo {}, Role::Host)
+            .await
+            .expect("update");
+        assert_eq!(t.room.state, GameState::Answer);
+        assert_eq!(t.room.current_question, Some((0, 0)));
+        assert_eq!(t.room.current_buzzer, Some(1));
+        assert_eq!(t.room.board_control, None);
+        assert!(!t.room.rounds[0].categories[0].questions[0].answered);
+        assert_eq!(t.room.players[0].player.score, 0);
+        assert!(t.room.players[0].player.buzzed);
+        assert!(matches!(
+            drain(&t.player_rx).await.as_slice(),
+            [
+                WsMsg::GameState {
+                    state: GameState::Answer,
+                    ..
+                },
+                WsMsg::PlayerState { score: 0, .. }
+            ]
+        ));
+        assert!(t.room.history.is_empty());
+        let undone = t.room.score_log.last().expect("undo is logged");
+        assert_eq!((undone.from, undone.to), (300, 0));
+        assert_eq!(undone.reason, UNDO_REASON);
+    }
+
+    #[tokio::test]
+    async fn picking_the_next_clue_clears_the_undo_history() {
+        let mut t = test_room();
+        t.room.rounds[0].categories[0].questions.push(Question {
+            text: "Longest river in Europe".to_string(),
+            answer: "The Volga".to_string(),
+            value: 400,
+            answered: false,
+            answer_seconds: None,
+            daily_double: false,
+        });
+        t.room.state = GameState::Answer;
+        t.room.current_question = Some((0, 0));
+        t.room.current_buzzer = Some(1);
+        let correct = WsMsg::HostChecked { correct: true };
+        t.room.update(&correct, Role::Host).await.expect("update");
+        assert_eq!(t.room.history.len(), 1);
+
+        let next = WsMsg::HostChoice {
+            category_index: 0,
+            question_index: 1,
+        };
+        t.room.update(&next, Role::Host).await.expect("update");
+        assert_eq!(t.room.current_question, Some((0, 1)));
+        assert!(t.room.history.is_empty());
+    }
+
+    #[tokio::test]
+    async fn voiding_a_clue_scores_nobody() {
+        let mut t = test_room();
+        t.room.state = GameState::Answer;
+        t.room.current_question = Some((0, 0));
+        t.room.current_buzzer = Some(1);
+        t.room.players[0].player.buzzed = true;
+
+        let back = WsMsg::VoidClue {
+            return_to_board: true,
+        };
+        t.room.update(&back, Role::Host).await.expect("update");
+        assert_eq!(t.room.state, GameState::Selection);
+        assert_eq!(t.room.current_question, None);
+        assert_eq!(t.room.current_buzzer, None);
+        assert!(!t.room.players[0].player.buzzed);
+        assert!(!t.room.rounds[0].categories[0].questions[0].answered);
+
+        // Nothing is open any more
+        t.room.update(&back, Role::Host).await.expect("update");
+        assert_eq!(t.room.history.len(), 1);
+
+        t.room.state = GameState::WaitingForBuzz;
+        t.room.current_question = Some((0, 0));
+        let answered = WsMsg::VoidClue {
+            return_to_board: false,
+        };
+        t.room.update(&answered, Role::Host).await.expect("update");
+        assert!(t.room.rounds[0].categories[0].questions[0].answered);
+        assert_eq!(t.room.state, GameState::GameEnd);
+        assert_eq!(t.room.players[0].player.score, 0);
+    }
+
+    #[tokio::test]
+    async fn the_answer_is_revealed_until_the_host_continues() {
+        let mut t = test_room();
+        t.room.settings.reveal_seconds = None;
+        t.room.state = GameState::Answer;
+        t.room.current_question = Some((0, 0));
+        t.room.current_buzzer = Some(1);
+        let correct = WsMsg::HostChecked { correct: true };
+        t.room.update(&correct, Role::Host).await.expect("update");
+
+        assert_eq!(t.room.state, GameState::Reveal);
+        assert!(t.room.timer.is_none());
+        let expected = Reveal {
+            answer: "The Nile".to_string(),
+            pid: Some(1),
+            name: Some("alice".to_string()),
+        };
+        let msgs = drain(&t.display_rx).await;
+        assert!(matches!(
+            msgs.as_slice(),
+            [WsMsg::AnswerRevealed { pid: Some(1), .. }, WsMsg::GameState { categories, reveal, .. }]
+            if categories[0].questions[0].answer == "The Nile" && reveal.as_ref() == Some(&expected)
+        ));
+
+        t.room
+            .update(&WsMsg::HostReady {}, Role::Host)
+            .await
+            .expect("update");
+        assert_eq!(t.room.state, GameState::GameEnd);
+        assert_eq!(t.room.reveal, None);
+        assert!(t.room.rounds[0].categories[0].questions[0].answered);
+    }
+
+    #[tokio::test]
+    async fn a_clue_is_only_judged_once() {
+        for second in [true, false] {
+            let mut t = test_room();
+            t.room.state = GameState::Answer;
+            t.room.current_question = Some((0, 0));
+            t.room.current_buzzer = Some(1);
+            t.room.players[0].player.buzzed = true;
+            let correct = WsMsg::HostChecked { correct: true };
+            t.room.update(&correct, Role::Host).await.expect("update");
+            drain(&t.host_rx).await;
+
+            let again = WsMsg::HostChecked { correct: second };
+            t.room.update(&again, Role::Host).await.expect("update");
+            assert_eq!(
+                error_code(&drain(&t.host_rx).await),
+                Some(ErrorCode::NothingToJudge)
+            );
+            assert_eq!(t.room.state, GameState::Reveal);
+            assert_eq!(t.room.players[0].player.score, 200);
+            assert_eq!(t.room.history.len(), 1);
+        }
+    }
+
+    #[tokio::test]
+    async fn a_pick_during_the_last_reveal_of_a_round_is_refused() {
+        let mut t = test_room();
+        let next_round = t.room.rounds[0].clone();
+        t.room.rounds.push(next_round);
+        t.room.settings.reveal_seconds = None;
+        t.room.state = GameState::Answer;
+        t.room.current_question = Some((0, 0));
+        t.room.current_buzzer = Some(1);
+        let correct = WsMsg::HostChecked { correct: true };
+        t.room.update(&correct, Role::Host).await.expect("update");
+        assert_eq!(t.room.state, GameState::Reveal);
+        drain(&t.host_rx).await;
+
+        let pick = WsMsg::HostChoice {
+            category_index: 0,
+            question_index: 0,
+        };
+        t.room.update(&pick, Role::Host).await.expect("update");
+        let msgs = drain(&t.host_rx).await;
+        assert!(matches!(
+            msgs.first(),
+            Some(WsMsg::Error {
+                code: ErrorCode::InvalidChoice,
+                ..
+            })
+        ));
+        assert_eq!(t.room.current_round, 1);
+        assert_eq!(t.room.state, GameState::Selection);
+        assert_eq!(t.room.current_question, None);
+        assert_eq!(t.room.board_control, Some(1));
+    }
+
+    #[tokio::test]
+    async fn the_player_in_control_picks_the_next_clue() {
+        let mut t = test_room();
+        t.room.state = GameState::Selection;
+        let pick = WsMsg::PlayerChoice {
+            category_index: 0,
+            question_index: 0,
+        };
+        t.room.update(&pick, Role::Player(1)).await.expect("update");
+        assert_eq!(
+            error_code(&drain(&t.player_rx).await),
+            Some(ErrorCode::NotInControl)
+        );
+
+        t.room.settings.player_control = true;
+        let missing = WsMsg::PlayerChoice {
+            category_index: 0,
+            question_index: 5,
+        };
+        t.room
+            .update(&missing, Role::Player(1))
+            .await
+            .expect("update");
+        assert_eq!(
+            error_code(&drain(&t.player_rx).await),
+            Some(ErrorCode::InvalidChoice)
+        );
+
+        // alice is the only player, so she holds control
+        t.room.update(&pick, Role::Player(1)).await.expect("update");
+        assert_eq!(t.room.state, GameState::QuestionReading);
+        assert!(matches!(
+            drain(&t.display_rx).await.as_slice(),
+            [WsMsg::GameState {
+                board_control: Some(1),
+                ..
+            }]
+        ));
+    }
+
+    #[tokio::test]
+    async fn reconnects_need_the_seats_own_token() {
+        let mut t = test_room();
+        let ip: IpAddr = [127, 0, 0, 1].into();
+        let (tx, rx) = channel(64);
+        for (pid, token) in [(Some(1), "wrong-token"), (Some(99), "s3cret-token")] {
+            let seat = t
+                .room
+                .reconnect_player(pid, token, &tx, ip)
+                .await
+                .expect("reconnect");
+            assert_eq!(seat, None, "{pid:?} {token}");
+        }
+        assert!(drain(&rx).await.is_empty());
+        assert!(!t.player_rx.is_closed(), "the seat's session carries on");
+        assert_eq!(t.room.players[0].token, "s3cret-token");
+
+        let seat = t
+            .room
+            .reconnect_player(Some(1), "s3cret-token", &tx, ip)
+            .await
+            .expect("reconnect");
+        assert_eq!(seat, Some(1));
+        assert_eq!(
+            error_code(&drain(&t.player_rx).await),
+            Some(ErrorCode::SessionReplaced)
+        );
+        assert!(t.player_rx.is_closed(), "the old session is ended");
+        let new_token = t.room.players[0].token.clone();
+        assert_ne!(new_token, "s3cret-token", "the token is rotated");
+        assert!(matches!(
+            drain(&rx).await.as_slice(),
+            [WsMsg::NewPlayer { pid: 1, token }, WsMsg::PlayerState { pid: 1, .. }]
+            if *token == new_token
+        ));
+
+        // The old token is no good any more
+        let (tx, _rx) = channel(64);
+        let seat = t
+            .room
+            .reconnect_player(None, "s3cret-token", &tx, ip)
+            .await
+            .expect("reconnect");
+        assert_eq!(seat, None);
+    }
+
+    #[tokio::test]
+    async fn the_host_can_hand_over_control() {
+        let mut t = test_room();
+        let (tx, _rx) = channel(64);
+        let pid = t.room.next_player_id();
+        t.room.players.push(PlayerEntry::new(
+            Player::new(pid, "carol".to_string()),
+            "carol-token".to_string(),
+            tx,
+        ));
+        t.room.players[1].player.score = -100;
+        assert_eq!(t.room.selecting_player(), Some(pid));
+
+        t.room
+            .update(&WsMsg::SetControl { pid: 1 }, Role::Host)
+            .await
+            .expect("update");
+        assert_eq!(t.room.selecting_player(), Some(1));
+        t.room
+            .update(&WsMsg::SetControl { pid: 99 }, Role::Host)
+            .await
+            .expect("update");
+        assert_eq!(t.room.board_control, Some(1));
+
+        // A correct ruling with nobody on the buzzer keeps the handover
+        t.room.state = GameState::Answer;
+        t.room.current_question = Some((0, 0));
+        let correct = WsMsg::HostChecked { correct: true };
+        t.room.update(&correct, Role::Host).await.expect("update");
+        assert_eq!(t.room.state, GameState::Reveal);
+        assert_eq!(t.room.board_control, Some(1));
+    }
+
+    #[tokio::test]
+    async fn the_ticker_sleeps_until_something_is_due() {
+        let mut t = test_room();
+        assert_eq!(t.room.next_tick(), None);
+
+        t.room.settings.answer_seconds = Some(3);
+        t.room.current_question = Some((0, 0));
+        t.room.set_state(GameState::Answer);
+        let due = t.room.next_tick().expect("timer armed");
+        assert!(
+            due <= Instant::now(),
+            "first TimerTick is due straight away"
+        );
+
+        t.room.tick().await.expect("tick");
+        let due = t.room.next_tick().expect("timer armed");
+        let wait = due.saturating_duration_since(Instant::now());
+        assert!(wait > Duration::from_millis(500) && wait <= Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn heartbeats_only_run_while_someone_is_connected() {
+        let mut t = test_room();
+        t.room.settings.heartbeat_interval_ms = 1_000;
+        t.room.settings.heartbeat_jitter_ms = 0;
+        assert!(
+            t.room.due_heartbeats().is_none(),
+            "first round is scheduled, not sent"
+        );
+        assert!(t.room.next_tick().is_some());
+
+        t.room.next_heartbeat = Some(Instant::now());
+        let heartbeats = t.room.due_heartbeats().expect("round is due");
+        assert_eq!(heartbeats.send().await, 0);
+        assert!(matches!(
+            drain(&t.player_rx).await.as_slice(),
+            [WsMsg::DoHeartbeat { .. }]
+        ));
+
+        t.room.players[0].player.status = ConnectionStatus::Disconnected;
+        assert!(t.room.due_heartbeats().is_none());
+        assert_eq!(
+            t.room.next_tick(),
+            None,
+            "an empty room has nothing to wake up for"
+        );
+    }
+}
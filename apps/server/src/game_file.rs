@@ -0,0 +1,209 @@
+use anyhow::{Result, anyhow};
+use serde::Deserialize;
+
+use crate::game::{Category, FinalQuestion, Question, Round};
+
+/// The game format the frontend builds and saves (see apps/web/sample_game.json).
+#[derive(Deserialize, Debug)]
+pub struct GameFile {
+    pub single: Vec<FileCategory>,
+    #[serde(default)]
+    pub double: Vec<FileCategory>,
+    #[serde(rename = "final")]
+    pub final_clue: Option<FileFinal>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct FileCategory {
+    pub category: String,
+    pub clues: Vec<FileClue>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct FileClue {
+    pub value: u32,
+    pub clue: String,
+    pub solution: String,
+    #[serde(default, rename = "dailyDouble")]
+    pub daily_double: bool,
+    #[serde(rename = "answerSeconds")]
+    pub answer_seconds: Option<u32>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct FileFinal {
+    pub category: String,
+    pub clue: String,
+    pub solution: String,
+}
+
+impl GameFile {
+    /// Checks the file and converts it into the rounds and final question a room plays.
+    /// Values in the file are already per-round, so no multiplier is applied.
+    pub fn into_rounds(self) -> Result<(Vec<Round>, Option<FinalQuestion>)> {
+        if self.single.is_empty() {
+            return Err(anyhow!("game.single must have at least one category"));
+        }
+        let mut rounds = vec![Round::new(convert_board("single", self.single)?)];
+        if !self.double.is_empty() {
+            rounds.push(Round::new(convert_board("double", self.double)?));
+        }
+
+        let final_question = match self.final_clue {
+            Some(FileFinal {
+                category,
+                clue,
+                solution,
+            }) => {
+                if category.trim().is_empty()
+                    || clue.trim().is_empty()
+                    || solution.trim().is_empty()
+                {
+                    return Err(anyhow!(
+                        "game.final needs a non-empty category, clue and solution"
+                    ));
+                }
+                Some(FinalQuestion {
+                    category,
+                    text: clue,
+                    answer: solution,
+                })
+            }
+            None => None,
+        };
+
+        Ok((rounds, final_question))
+    }
+}
+
+fn convert_board(section: &str, board: Vec<FileCategory>) -> Result<Vec<Category>> {
+    board
+        .into_iter()
+        .enumerate()
+        .map(|(cat_idx, FileCategory { category, clues })| {
+            if category.trim().is_empty() {
+                return Err(anyhow!("game.{section}[{cat_idx}].category is empty"));
+            }
+            if clues.is_empty() {
+                return Err(anyhow!("game.{section}[{cat_idx}] has no clues"));
+            }
+            let questions = clues
+                .into_iter()
+                .enumerate()
+                .map(|(clue_idx, clue)| {
+                    if clue.clue.trim().is_empty() || clue.solution.trim().is_empty() {
+                        return Err(anyhow!(
+                            "game.{section}[{cat_idx}].clues[{clue_idx}] needs a non-empty clue and solution"
+                        ));
+                    }
+                    Ok(Question {
+                        text: clue.clue,
+                        answer: clue.solution,
+                        value: clue.value,
+                        answered: false,
+                        daily_double: clue.daily_double,
+                        answer_seconds: clue.answer_seconds,
+                    })
+                })
+                .collect::<Result<Vec<_>>>()?;
+            Ok(Category {
+                title: category,
+                questions,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use axum::{body::Bytes, extract::State};
+    use http::StatusCode;
+    use serde_json::json;
+
+    use super::*;
+    use crate::{AppState, create_room, names::Blocklist};
+
+    /// The files the web client ships, wrapped in "game" like the create request.
+    #[derive(Deserialize)]
+    struct Saved {
+        game: GameFile,
+    }
+
+    fn parse(text: &str) -> GameFile {
+        serde_json::from_str::<Saved>(text)
+            .expect("valid game file")
+            .game
+    }
+
+    fn error_of(game: serde_json::Value) -> String {
+        let game: GameFile = serde_json::from_value(game).expect("well-formed game");
+        match game.into_rounds() {
+            Ok(_) => panic!("accepted a bad game"),
+            Err(e) => e.to_string(),
+        }
+    }
+
+    fn clue(clue: &str, solution: &str) -> serde_json::Value {
+        json!({ "value": 200, "clue": clue, "solution": solution })
+    }
+
+    #[test]
+    fn the_web_clients_game_files_parse_as_is() {
+        let sample = parse(include_str!("../../web/sample_game.json"));
+        let (rounds, final_question) = sample.into_rounds().expect("sample game");
+        assert_eq!(rounds.len(), 1);
+        assert_eq!(rounds[0].categories.len(), 6);
+        assert!(final_question.is_none());
+
+        let formatted = parse(include_str!("../../web/public/formatted_game.json"));
+        let (rounds, final_question) = formatted.into_rounds().expect("formatted game");
+        assert_eq!(rounds.len(), 1, "an empty double board adds no round");
+        let final_question = final_question.expect("final clue");
+        assert_eq!(final_question.answer, "What is an AVL tree?");
+    }
+
+    #[test]
+    fn bad_boards_say_what_is_wrong() {
+        assert_eq!(
+            error_of(json!({ "single": [] })),
+            "game.single must have at least one category"
+        );
+        assert_eq!(
+            error_of(json!({ "single": [{ "category": " ", "clues": [clue("a", "b")] }] })),
+            "game.single[0].category is empty"
+        );
+        assert_eq!(
+            error_of(json!({
+                "single": [{ "category": "A", "clues": [clue("a", "b")] }],
+                "double": [{ "category": "B", "clues": [] }],
+            })),
+            "game.double[0] has no clues"
+        );
+        assert_eq!(
+            error_of(json!({
+                "single": [{ "category": "A", "clues": [clue("a", "b"), clue("c", "")] }],
+            })),
+            "game.single[0].clues[1] needs a non-empty clue and solution"
+        );
+        assert_eq!(
+            error_of(json!({
+                "single": [{ "category": "A", "clues": [clue("a", "b")] }],
+                "final": { "category": "F", "clue": "", "solution": "s" },
+            })),
+            "game.final needs a non-empty category, clue and solution"
+        );
+    }
+
+    #[tokio::test]
+    async fn create_room_tells_bad_json_from_bad_content() {
+        let state = Arc::new(AppState::new(Blocklist::default(), None));
+        let malformed = create_room(State(state.clone()), Bytes::from_static(b"{\"game\":")).await;
+        assert_eq!(malformed.status(), StatusCode::BAD_REQUEST);
+        let empty = Bytes::from_static(b"{\"game\":{\"single\":[]}}");
+        let invalid = create_room(State(state.clone()), empty).await;
+        assert_eq!(invalid.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert!(state.room_map.lock().await.is_empty());
+    }
+}
@@ -1,19 +1,25 @@
-use std::{collections::HashMap, sync::Arc, time::Duration};
+use std::{
+    collections::HashMap,
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+    time::Duration,
+};
 
 use anyhow::{Result, anyhow};
 use axum::{
     Json, Router,
+    body::Bytes,
     extract::{
-        Path, Query, State,
+        ConnectInfo, Path, Query, State,
         ws::{Message, Utf8Bytes, WebSocket, WebSocketUpgrade},
     },
-    response::Response,
+    response::{IntoResponse, Response},
     routing::{any, get, post},
 };
 use tower_http::services::{ServeDir, ServeFile};
 
 use futures::{FutureExt, select};
-use http::StatusCode;
+use http::{HeaderMap, HeaderName, StatusCode};
 use rand::Rng;
 use serde::{Deserialize, Serialize};
 
@@ -21,25 +27,39 @@ use tokio::sync::Mutex;
 use tokio_mpmc::channel;
 
 use crate::{
-    game::{GameState, Room},
+    game::{Audience, GameState, Role, Room, end_session},
     host::HostEntry,
-    player::{Player, PlayerEntry},
-    ws_msg::WsMsg,
+    player::{ConnectionStatus, Player, PlayerEntry},
+    rate_limit::{ConnectionLimiter, Verdict},
+    tokens::{generate_token, tokens_match},
+    ws_msg::{ErrorCode, WsMsg},
 };
 
+mod admin;
+mod client_ip;
+mod clock;
+mod display;
 mod game;
+mod game_file;
 mod host;
+mod names;
 mod player;
+mod rate_limit;
+mod tokens;
 mod ws_msg;
 
 struct AppState {
     room_map: Mutex<HashMap<String, Room>>,
+    name_blocklist: names::Blocklist,
+    proxy_header: Option<HeaderName>, // where a trusted reverse proxy puts client addresses
 }
 
 impl AppState {
-    pub fn new() -> Self {
+    pub fn new(name_blocklist: names::Blocklist, proxy_header: Option<HeaderName>) -> Self {
         Self {
             room_map: Mutex::new(HashMap::new()),
+            name_blocklist,
+            proxy_header,
         }
     }
 }
@@ -55,43 +75,66 @@ fn generate_room_code() -> String {
         .collect()
 }
 
-fn generate_host_token() -> String {
-    const CHARSET: &[u8] = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789";
-    let mut rng = rand::rng();
-    (0..32)
-        .map(|_| {
-            let idx = rng.random_range(0..CHARSET.len());
-            CHARSET[idx] as char
-        })
-        .collect()
-}
-
-fn generate_player_token() -> String {
-    const CHARSET: &[u8] = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789";
-    let mut rng = rand::rng();
-    (0..32)
-        .map(|_| {
-            let idx = rng.random_range(0..CHARSET.len());
-            CHARSET[idx] as char
-        })
-        .collect()
-}
-
 #[derive(Serialize)]
 struct CreateRoomResponse {
     room_code: String,
     host_token: String,
+    display_token: String, // for read-only projection screens
+}
+
+#[derive(Serialize)]
+struct ErrorResponse {
+    error: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    code: Option<ErrorCode>, // machine-readable reason, where there is one
+}
+
+fn error_response(status: StatusCode, error: String) -> Response {
+    (status, Json(ErrorResponse { error, code: None })).into_response()
+}
+
+fn coded_error_response(status: StatusCode, code: ErrorCode) -> Response {
+    let body = ErrorResponse {
+        error: code.to_string(),
+        code: Some(code),
+    };
+    (status, Json(body)).into_response()
 }
 
 #[derive(Deserialize)]
 struct CreateRoomRequest {
-    categories: Option<Vec<game::Category>>,
+    game: Option<game_file::GameFile>, // the frontend's saved game format, takes precedence
+    categories: Option<Vec<game::Category>>, // shorthand for a single round
+    rounds: Option<Vec<game::Round>>,
+    #[serde(rename = "finalQuestion")]
+    final_question: Option<game::FinalQuestion>,
+    settings: Option<game::RoomSettings>,
+    #[serde(rename = "joinPassword")]
+    join_password: Option<String>, // required from new players, not from reconnects
 }
 
-async fn create_room(
-    State(state): State<Arc<AppState>>,
-    Json(body): Json<CreateRoomRequest>,
-) -> (StatusCode, Json<CreateRoomResponse>) {
+async fn create_room(State(state): State<Arc<AppState>>, body: Bytes) -> Response {
+    // Parsed by hand so bad game files get a readable error instead of axum's rejection
+    let body: CreateRoomRequest = match serde_json::from_slice(&body) {
+        Ok(body) => body,
+        Err(e) => {
+            return error_response(StatusCode::BAD_REQUEST, format!("Invalid game JSON: {e}"));
+        }
+    };
+    let (rounds, final_question) = match body.game {
+        Some(game) => match game.into_rounds() {
+            Ok((rounds, final_question)) => (rounds, final_question),
+            Err(e) => {
+                return error_response(StatusCode::UNPROCESSABLE_ENTITY, format!("{e}"));
+            }
+        },
+        None => match (body.rounds, body.categories) {
+            (Some(rounds), _) => (rounds, body.final_question),
+            (None, Some(categories)) => (vec![game::Round::new(categories)], body.final_question),
+            (None, None) => (Vec::new(), body.final_question),
+        },
+    };
+
     let mut room_map = state.room_map.lock().await;
 
     // Generate a unique room code
@@ -102,28 +145,71 @@ async fn create_room(
         }
     };
 
-    let host_token = generate_host_token();
-    let mut room = Room::new(code.clone(), host_token.clone());
+    let host_token = generate_token();
+    let display_token = generate_token();
+    let mut room = Room::new(code.clone(), host_token.clone(), display_token.clone());
 
-    if let Some(categories) = body.categories {
-        room.categories = categories;
-    }
+    room.rounds = rounds;
+    room.final_question = final_question;
+    room.settings = body.settings.unwrap_or_default();
+    room.join_password = body.join_password.filter(|p| !p.is_empty());
 
     room_map.insert(code.clone(), room);
+    spawn_room_ticker(state.clone(), code.clone());
 
     (
         StatusCode::CREATED,
         Json(CreateRoomResponse {
             room_code: code,
             host_token,
+            display_token,
         }),
     )
+        .into_response()
 }
 
-#[derive(Debug)]
-enum ConnectionStatus {
-    Connected,
+/// Rooms with no messages or connections for this long are closed.
+const ROOM_IDLE_TIMEOUT: Duration = Duration::from_secs(60 * 60);
+
+/// Drives a room's server-side timers until the room is gone. Sleeps until the next deadline
+/// or until the room wakes it, and closes the room once it has been idle too long.
+fn spawn_room_ticker(state: Arc<AppState>, code: String) {
+    tokio::spawn(async move {
+        loop {
+            let (wake, next, heartbeats) = {
+                let mut room_map = state.room_map.lock().await;
+                let Some(room) = room_map.get_mut(&code) else {
+                    break;
+                };
+                if room.last_activity.elapsed() >= ROOM_IDLE_TIMEOUT {
+                    if let Some(room) = room_map.remove(&code) {
+                        println!("room {code} was idle, closing it");
+                        close_room(&room, ErrorCode::RoomExpired).await;
+                    }
+                    break;
+                }
+                if let Err(e) = room.tick().await {
+                    println!("room {code} tick failed, continuing anyway: {e}");
+                }
+                let heartbeats = room.due_heartbeats();
+                let expires = room.last_activity + ROOM_IDLE_TIMEOUT;
+                let next = room.next_tick().map_or(expires, |at| at.min(expires));
+                (room.wake.clone(), next, heartbeats)
+            };
+            if let Some(heartbeats) = heartbeats {
+                let failures = heartbeats.send().await;
+                if failures > 0 {
+                    println!("room {code}: {failures} scheduled heartbeat(s) were skipped");
+                }
+            }
+            tokio::select! {
+                () = tokio::time::sleep_until(next.into()) => {}
+                () = wake.notified() => {}
+            }
+        }
+    });
 }
+
 #[derive(Serialize, Deserialize)]
 struct RoomParams {
     code: String,
@@ -136,37 +222,107 @@ struct WsQuery {
     token: Option<String>, // only rejoining players include both token & player_id
     #[serde(rename = "playerID")]
     player_id: Option<u32>,
+    password: Option<String>, // room join password, new players only
 }
 
 async fn ws_upgrade_handler(
     State(state): State<Arc<AppState>>,
     ws_upgrade: WebSocketUpgrade,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
     Path(rp @ RoomParams { .. }): Path<RoomParams>,
     Query(WsQuery {
         token,
-        player_name,
+        mut player_name,
         player_id,
+        password,
     }): Query<WsQuery>,
 ) -> Response {
-    ws_upgrade.on_upgrade(async move |ws| {
-        match ws_socket_handler(
-            ws,
-            rp,
-            state,
-            WsQuery {
-                player_name,
-                token,
-                player_id,
-            },
-        )
-        .await
-        {
-            Ok(()) => {}
-            Err(e) => {
-                println!("WebSocket handler failed (died but didn't panic): {e}");
+    let ip = client_ip::client_ip(&headers, peer, state.proxy_header.as_ref());
+    {
+        let room_map = state.room_map.lock().await;
+        if let Some(room) = room_map.get(&rp.code) {
+            let privileged = token.as_ref().is_some_and(|tok| {
+                tokens_match(tok, &room.host_token) || tokens_match(tok, &room.display_token)
+            });
+            // Same precedence as ws_socket_handler: pid + token is a reconnect, a name is a join
+            let joining =
+                !privileged && player_name.is_some() && !(player_id.is_some() && token.is_some());
+            if joining && let Some(raw) = &player_name {
+                let name = match names::normalize(raw) {
+                    Ok(name) => name,
+                    Err(code) => {
+                        return coded_error_response(StatusCode::UNPROCESSABLE_ENTITY, code);
+                    }
+                };
+                if let Some(part) = state.name_blocklist.blocked_part(&name) {
+                    let body = ErrorResponse {
+                        error: format!("\"{part}\" isn't allowed in names, please pick another"),
+                        code: Some(ErrorCode::NameBlocked),
+                    };
+                    return (StatusCode::UNPROCESSABLE_ENTITY, Json(body)).into_response();
+                }
+                player_name = Some(name);
+            }
+            if !privileged
+                && room
+                    .bans
+                    .blocks(token.as_deref(), player_name.as_deref(), ip)
+            {
+                return error_response(
+                    StatusCode::FORBIDDEN,
+                    "You are banned from this room".to_string(),
+                );
+            }
+            if joining && let Some(expected) = &room.join_password {
+                match &password {
+                    Some(given) if tokens_match(given, expected) => {}
+                    Some(_) => {
+                        return error_response(
+                            StatusCode::UNAUTHORIZED,
+                            "Wrong room password".to_string(),
+                        );
+                    }
+                    None => {
+                        return error_response(
+                            StatusCode::UNAUTHORIZED,
+                            "This room needs a password".to_string(),
+                        );
+                    }
+                }
+            }
+            if joining
+                && let Some(name) = &player_name
+                && let Err(code) = room.claim_name(name)
+            {
+                return coded_error_response(StatusCode::CONFLICT, code);
             }
         }
-    })
+    }
+    ws_upgrade
+        .max_message_size(rate_limit::MAX_SOCKET_MESSAGE_BYTES)
+        .max_frame_size(rate_limit::MAX_SOCKET_MESSAGE_BYTES)
+        .on_upgrade(async move |ws| {
+            match ws_socket_handler(
+                ws,
+                rp,
+                state,
+                WsQuery {
+                    player_name,
+                    token,
+                    player_id,
+                    password,
+                },
+                ip,
+            )
+            .await
+            {
+                Ok(()) => {}
+                Err(e) => {
+                    println!("WebSocket handler failed (died but didn't panic): {e}");
+                }
+            }
+        })
 }
 
 async fn send_player_list_to_host(host: &HostEntry, players: &[PlayerEntry]) -> anyhow::Result<()> {
@@ -177,6 +333,31 @@ async fn send_player_list_to_host(host: &HostEntry, players: &[PlayerEntry]) ->
     Ok(())
 }
 
+/// Tells a flooding client why it is being dropped, the error ends its handler.
+async fn rate_limited(ws: &mut WebSocket, role: Role) -> anyhow::Error {
+    if let Ok(ser) = serde_json::to_string(&WsMsg::error(ErrorCode::RateLimited)) {
+        let _ = ws.send(Message::Text(Utf8Bytes::from(ser))).await;
+    }
+    let _ = ws.send(Message::Close(None)).await;
+    anyhow!("{role:?} disconnected for exceeding rate limits")
+}
+
+/// Ends every session in a room that was just removed from the room map.
+async fn close_room(room: &Room, reason: ErrorCode) {
+    let senders = room
+        .host
+        .iter()
+        .map(|h| &h.sender)
+        .chain(room.players.iter().map(|p| &p.sender))
+        .chain(room.displays.iter().map(|d| &d.sender))
+        .chain(room.pending.iter().map(|p| &p.sender));
+    for sender in senders {
+        end_session(sender, reason).await;
+    }
+    // Its ticker finds the room gone and stops
+    room.wake.notify_one();
+}
+
 async fn ws_socket_handler(
     mut ws: WebSocket,
     RoomParams { code }: RoomParams,
@@ -185,57 +366,59 @@ async fn ws_socket_handler(
         player_name,
         token,
         player_id,
+        ..
     }: WsQuery,
+    ip: IpAddr,
 ) -> anyhow::Result<()> {
     // for debugging
     println!("{:?} {:?} {:?} {:?}", code, token, player_name, player_id);
     let ch: tokio_mpmc::Receiver<WsMsg>;
     let tx: tokio_mpmc::Sender<WsMsg>;
     (tx, ch) = channel(20);
-    let mut connection_player_id: Option<u32> = player_id;
-    let tx_internal = tx.clone();
+    let mut role: Role;
+    let mut limiter: ConnectionLimiter;
     {
         let mut room_map = state.room_map.lock().await;
         let room = room_map
             .get_mut(&code)
             .ok_or_else(|| anyhow!("Room {} does not exist", code))?;
-        println!("room: {:?}", room);
+        limiter = ConnectionLimiter::new(room.limit_counters.clone());
+        room.touch();
 
-        let is_host = token.as_ref() == Some(&room.host_token);
+        let is_host = token
+            .as_ref()
+            .is_some_and(|tok| tokens_match(tok, &room.host_token));
+        let is_display = token
+            .as_ref()
+            .is_some_and(|tok| tokens_match(tok, &room.display_token));
 
         if is_host {
+            if let Some(old_host) = &room.host {
+                end_session(&old_host.sender, ErrorCode::SessionReplaced).await;
+            }
             let host = HostEntry::new(player_id.unwrap_or(0), tx.clone());
             send_player_list_to_host(&host, &room.players).await?;
+            if !room.pending.is_empty() {
+                tx.send(room.pending_list_msg()).await?;
+            }
 
             if room.state != GameState::Start {
-                let players: Vec<Player> = room.players.iter().map(|e| e.player.clone()).collect();
-                let game_state_msg = WsMsg::GameState {
-                    state: room.state.clone(),
-                    categories: room.categories.clone(),
-                    players,
-                    current_question: room.current_question,
-                    current_buzzer: room.current_buzzer,
-                };
-                tx.send(game_state_msg).await?;
+                tx.send(room.game_state_msg(Audience::Host)).await?;
             }
 
             room.host = Some(host);
-        } else if let (Some(id), Some(_tok)) = (player_id, &token) {
-            if let Some(existing) = room.players.iter_mut().find(|p| p.player.pid == id) {
-                // Update existing player's send channel
-                existing.sender = tx.clone();
-
-                let can_buzz = room.state == GameState::WaitingForBuzz;
-                let player_state_msg = WsMsg::PlayerState {
-                    pid: existing.player.pid,
-                    buzzed: existing.player.buzzed,
-                    score: existing.player.score,
-                    can_buzz,
-                };
-                tx.send(player_state_msg).await?;
+            role = Role::Host;
+        } else if is_display {
+            // Displays never count as players, any number may share the token
+            role = Role::Display(room.attach_display(tx.clone()));
+            tx.send(room.game_state_msg(Audience::Display)).await?;
+        } else if let (Some(id), Some(tok)) = (player_id, &token) {
+            if let Some(pid) = room.reconnect_player(Some(id), tok, &tx, ip).await? {
+                role = Role::Player(pid);
             } else {
+                // Same error for unknown pids and wrong tokens, so pids can't be probed
                 return Err(anyhow!(
-                    "Player with ID {} could not be found in room {}",
+                    "Invalid reconnect for player {} in room {}",
                     id,
                     code
                 ));
@@ -244,38 +427,37 @@ async fn ws_socket_handler(
                 send_player_list_to_host(host, &room.players).await?;
             }
         } else if let Some(name) = player_name {
-            let new_id = (room.players.len() + 1).try_into()?;
-            connection_player_id = Some(new_id);
-            let player_token = generate_player_token();
-            let player = PlayerEntry::new(
-                Player::new(new_id, name, 0, false, player_token.clone()),
-                tx.clone(),
-            );
-            room.players.push(player);
-
-            let new_player_msg = WsMsg::NewPlayer {
-                pid: new_id,
-                token: player_token,
-            };
-            tx.send(new_player_msg).await?;
+            // Checked before the upgrade too, but another join may have taken it since
+            let name = room.claim_name(&name).map_err(|code| anyhow!("{code}"))?;
+            if room.settings.require_approval {
+                // Wait for the host, the seat is created by AdmitPlayer
+                role = Role::Pending(room.add_pending(name, tx.clone(), ip));
+                tx.send(WsMsg::AwaitingApproval {}).await?;
+                if let Some(host) = &room.host {
+                    host.sender.send(room.pending_list_msg()).await?;
+                }
+            } else {
+                let new_id = room.next_player_id();
+                role = Role::Player(new_id);
+                let player_token = generate_token();
+                let mut player =
+                    PlayerEntry::new(Player::new(new_id, name), player_token.clone(), tx.clone());
+                player.ip = Some(ip);
+                room.players.push(player);
+
+                let new_player_msg = WsMsg::NewPlayer {
+                    pid: new_id,
+                    token: player_token,
+                };
+                tx.send(new_player_msg).await?;
 
-            if let Some(host) = &room.host {
-                send_player_list_to_host(host, &room.players).await?;
+                if let Some(host) = &room.host {
+                    send_player_list_to_host(host, &room.players).await?;
+                }
             }
         } else if let Some(tok) = &token {
-            if let Some(existing) = room.players.iter_mut().find(|p| p.player.token == *tok) {
-                connection_player_id = Some(existing.player.pid);
-                existing.sender = tx.clone();
-
-                let can_buzz = room.state == GameState::WaitingForBuzz;
-                let player_state_msg = WsMsg::PlayerState {
-                    pid: existing.player.pid,
-                    buzzed: existing.player.buzzed,
-                    score: existing.player.score,
-                    can_buzz,
-                };
-
-                tx.send(player_state_msg).await?;
+            if let Some(pid) = room.reconnect_player(None, tok, &tx, ip).await? {
+                role = Role::Player(pid);
             } else {
                 return Err(anyhow!("Invalid player token"));
             }
@@ -289,122 +471,140 @@ async fn ws_socket_handler(
         for player in &room.players {
             println!("player: {}", player.player.pid);
         }
+        if let Role::Player(_) = role {
+            // A first connected player starts the heartbeat rounds
+            room.wake.notify_one();
+        }
     }
-    loop {
-        select! {
-            res = ch.recv().fuse() => match res {
-                Ok(recv) => {
-                    let ser = serde_json::to_string(&recv)?;
-                    if let Some(r) = &recv {
-                        match &r {
+    let res: anyhow::Result<()> = async {
+        loop {
+            select! {
+                res = ch.recv().fuse() => match res {
+                    Ok(None) => {
+                        // The server ended this session, e.g. a newer connection took over
+                        let _ = ws.send(Message::Close(None)).await;
+                        break;
+                    },
+                    Ok(Some(recv)) => {
+                        // The host admitted this pending connection
+                        if let (Role::Pending(_), WsMsg::NewPlayer { pid, .. }) = (role, &recv) {
+                            role = Role::Player(*pid);
+                        }
+                        let ser = serde_json::to_string(&recv)?;
+                        match &recv {
                             WsMsg::GameState { state, .. } => println!("sending GameState: {:?}", state),
                             other => println!("sending {:?}", other),
                         }
-                    }
-                    ws.send(Message::Text(Utf8Bytes::from(ser))).await?;
+                        ws.send(Message::Text(Utf8Bytes::from(ser))).await?;
+                    },
+                    Err(e) => Err(e)?
                 },
-                Err(e) => Err(e)?
-            },
-            msg_opt = ws.recv().fuse() => match msg_opt {
-                None => break,
-                Some(msg) => {
-                    let msg = if let Ok(msg) = msg {
-                        msg
-                    } else {
-                        // client disconnected
-                        Err(std::io::Error::new(
-                            std::io::ErrorKind::HostUnreachable,
-                            "websocket client disconnected in read",
-                        ))?
-                    };
-                    let msg: String = msg.into_text()?.to_string();
-                    // deser
-                    let msg: WsMsg = serde_json::from_str(&msg)?;
-                    // witness case, just for now
-                    if let m @ (WsMsg::StartGame {}
-                        | WsMsg::EndGame {}
-                        | WsMsg::BuzzEnable {}
-                        | WsMsg::BuzzDisable {}
-                        | WsMsg::Buzz {}) = msg.clone() {
-                        let witness = WsMsg::Witness { msg: Box::new(m) };
+                msg_opt = ws.recv().fuse() => match msg_opt {
+                    None => break,
+                    Some(msg) => {
+                        let msg = if let Ok(msg) = msg {
+                            msg
+                        } else {
+                            // client disconnected
+                            Err(std::io::Error::new(
+                                std::io::ErrorKind::HostUnreachable,
+                                "websocket client disconnected in read",
+                            ))?
+                        };
+                        let text = match msg {
+                            Message::Text(text) => text,
+                            Message::Binary(data) => Utf8Bytes::try_from(data).unwrap_or_default(),
+                            Message::Ping(_) | Message::Pong(_) => {
+                                // answered by axum, but still count toward the frame limit
+                                if limiter.check_frame(0) == Verdict::Disconnect {
+                                    return Err(rate_limited(&mut ws, role).await);
+                                }
+                                continue;
+                            }
+                            Message::Close(_) => break,
+                        };
+                        // Limits are checked before parsing or touching the room lock
+                        let verdict = match limiter.check_frame(text.len()) {
+                            Verdict::Allow => match serde_json::from_str::<WsMsg>(&text) {
+                                Ok(msg) => match limiter.check_msg(&msg) {
+                                    Verdict::Allow => Ok(msg),
+                                    verdict => Err(verdict),
+                                },
+                                Err(_) => Err(limiter.invalid()),
+                            },
+                            verdict => Err(verdict),
+                        };
+                        let msg = match verdict {
+                            Ok(msg) => msg,
+                            Err(Verdict::Disconnect) => return Err(rate_limited(&mut ws, role).await),
+                            Err(_) => continue,
+                        };
+                        // witness case, just for now
+                        if role.authorize(&msg).is_ok() && let m @ (WsMsg::StartGame {}
+                            | WsMsg::EndGame {}
+                            | WsMsg::BuzzEnable {}
+                            | WsMsg::BuzzDisable {}
+                            | WsMsg::Buzz { .. }) = msg.clone() {
+                            let witness = WsMsg::Witness { msg: Box::new(m) };
+                            let mut room_map = state.room_map.lock().await;
+                            let room = room_map
+                                .get_mut(&code)
+                                .ok_or_else(|| anyhow!("Room {} does not exist", code))?;
+                            for player in &room.players {
+                                let cpid = player.player.pid;
+                                let csender = player.sender.clone();
+                                let lat: u64 = player.latency().into();
+                                let witnessc = witness.clone();
+                                let latc = lat;
+                                let sender_pid = role.pid();
+                                tokio::spawn(async move {
+                                    if sender_pid == Some(cpid) {
+                                            return Ok(());
+                                        }
+                                    let s = csender;
+                                    tokio::time::sleep(Duration::from_millis(500_u64.saturating_sub(latc))).await;
+                                    s.send(witnessc).await
+                                });
+                            }
+                        }
+                        // everything else
                         let mut room_map = state.room_map.lock().await;
                         let room = room_map
                             .get_mut(&code)
                             .ok_or_else(|| anyhow!("Room {} does not exist", code))?;
-                        for player in &room.players {
-                            let cpid = player.player.pid;
-                            let csender = player.sender.clone();
-                            let lat: u64 = player.latency()?.into();
-                            let witnessc = witness.clone();
-                            let latc = lat;
-                            tokio::spawn(async move {
-                                if let Some(id) = connection_player_id
-                                    && cpid == id {
-                                        return Ok(());
-                                    }
-                                let s = csender;
-                                tokio::time::sleep(Duration::from_millis(500 - latc)).await;
-                                s.send(witnessc).await
-                            });
-                        }
-                    }
-                    // heartbeat case
-                    if let WsMsg::Heartbeat { hbid, .. } = msg.clone() {
-                        tx_internal.send(WsMsg::GotHeartbeat { hbid }).await?;
-                        //continue;
+                        room.update(&msg, role).await?;
                     }
-                    // everything else
-                    let mut room_map = state.room_map.lock().await;
-                    let room = room_map
-                        .get_mut(&code)
-                        .ok_or_else(|| anyhow!("Room {} does not exist", code))?;
-                    room.update(&msg, connection_player_id).await?;
                 }
             }
         }
+        Ok(())
     }
-    Ok(())
-}
+    .await;
 
-//#[debug_handler]
-async fn cpr_handler(
-    State(state): State<Arc<AppState>>,
-    Path(rp @ RoomParams { .. }): Path<RoomParams>,
-) -> String {
-    let code = rp.code;
-    let res = {
-        let mut room_map = state.room_map.lock().await;
-        let room_res = room_map
-            .get_mut(&code)
-            .ok_or_else(|| anyhow!("Room {} does not exist", code));
-        let mut failures = 0_u32;
-        match room_res {
-            Err(e) => Err(e),
-            Ok(room) => {
-                for entry in &mut room.players {
-                    match entry.heartbeat().await {
-                        Ok(()) => {}
-                        Err(e) => {
-                            println!("cpr_handler heartbeat failure, did not panic: {e}");
-                            failures += 1;
-                        }
-                    }
+    let mut room_map = state.room_map.lock().await;
+    if let Some(room) = room_map.get_mut(&code) {
+        match role {
+            Role::Display(id) => room.detach_display(id),
+            Role::Pending(id) => {
+                if room.remove_pending(id)
+                    && let Some(host) = &room.host
+                {
+                    let _ = host.sender.send(room.pending_list_msg()).await;
                 }
-                Ok(format!(
-                    "Ok, requested {} heartbeats, {} failed immediately",
-                    room.players.len(),
-                    failures
-                ))
             }
-        }
-    };
-    match res {
-        Ok(s) => s,
-        Err(e) => {
-            println!("cpr_handler failure, did not panic: {e}");
-            format!("Err, {e}")
+            // A replaced session's channel is already closed, the seat belongs to the newer one
+            Role::Player(pid) if !tx.is_closed() => {
+                if let Some(entry) = room.players.iter_mut().find(|p| p.player.pid == pid) {
+                    entry.player.status = ConnectionStatus::Disconnected;
+                }
+                if let Some(host) = &room.host {
+                    let _ = send_player_list_to_host(host, &room.players).await;
+                }
+            }
+            _ => {}
         }
     }
+    res
 }
 
 const HOST: &str = "0.0.0.0";
@@ -412,12 +612,28 @@ const PORT: u16 = 3000;
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    let state = Arc::new(AppState::new());
+    let blocklist_path = std::env::var(names::BLOCKLIST_ENV)
+        .unwrap_or_else(|_| names::DEFAULT_BLOCKLIST_PATH.to_string());
+    let name_blocklist = names::Blocklist::load(std::path::Path::new(&blocklist_path))?;
+    println!(
+        "Loaded {} blocked name term(s) from {}",
+        name_blocklist.len(),
+        blocklist_path
+    );
+    let proxy_header = client_ip::proxy_header_from_env();
+    match &proxy_header {
+        Some(header) => println!("Taking client addresses from the {header} header"),
+        None => println!(
+            "{} is not set, IP bans use the peer address (behind a proxy that is the proxy's)",
+            client_ip::PROXY_HEADER_ENV
+        ),
+    }
+    let state = Arc::new(AppState::new(name_blocklist, proxy_header));
 
     let room_routes = Router::new()
         .route("/create", post(create_room))
         .route("/{code}/ws", any(ws_upgrade_handler))
-        .route("/{code}/cpr", get(cpr_handler))
+        .merge(admin::routes())
         .with_state(state);
 
     let api_routes = Router::new().nest("/rooms", room_routes);
@@ -431,9 +647,12 @@ async fn main() -> Result<()> {
 
     let listener = tokio::net::TcpListener::bind(format!("{}:{}", HOST, PORT)).await?;
     println!("Server running on http://{}:{}", HOST, PORT);
-    axum::serve(listener, app)
-        .await
-        .expect("Failed to start server");
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await
+    .expect("Failed to start server");
     Ok(())
 }
 
@@ -0,0 +1,154 @@
+use std::{fs, io, ops::Range, path::Path};
+
+use unicode_normalization::UnicodeNormalization;
+
+use crate::ws_msg::ErrorCode;
+
+/// Longest allowed name, in characters after normalisation.
+pub const MAX_NAME_CHARS: usize = 24;
+/// Anything longer than this is refused before normalising.
+const MAX_RAW_NAME_BYTES: usize = 256;
+
+/// Environment variable naming the blocklist file.
+pub const BLOCKLIST_ENV: &str = "NAME_BLOCKLIST";
+pub const DEFAULT_BLOCKLIST_PATH: &str = "name_blocklist.txt";
+
+/// Cleans up a requested player name: NFKC, no control or zero-width characters,
+/// whitespace trimmed and collapsed.
+pub fn normalize(raw: &str) -> Result<String, ErrorCode> {
+    if raw.len() > MAX_RAW_NAME_BYTES {
+        return Err(ErrorCode::NameTooLong);
+    }
+    let cleaned: String = raw
+        .nfkc()
+        .filter(|c| !c.is_control() && !is_invisible(*c))
+        .collect();
+    let name = cleaned.split_whitespace().collect::<Vec<_>>().join(" ");
+    match name.chars().count() {
+        0 => Err(ErrorCode::NameEmpty),
+        n if n > MAX_NAME_CHARS => Err(ErrorCode::NameTooLong),
+        _ => Ok(name),
+    }
+}
+
+/// What two names are compared by when checking for duplicates.
+pub fn name_key(name: &str) -> String {
+    name.to_lowercase()
+}
+
+fn is_invisible(c: char) -> bool {
+    matches!(c, '\u{200B}'..='\u{200F}' | '\u{2060}'..='\u{2064}' | '\u{FEFF}')
+}
+
+/// Lowercase letters and digits only, so "B a d" and "b.a.d" both match "bad".
+fn squash(s: &str) -> String {
+    s.nfkc()
+        .flat_map(char::to_lowercase)
+        .filter(|c| c.is_alphanumeric())
+        .collect()
+}
+
+/// The words of a name with where they are in it, squashed.
+fn words(name: &str) -> Vec<(Range<usize>, String)> {
+    let mut words = Vec::new();
+    let mut start = None;
+    for (i, c) in name.char_indices().chain([(name.len(), ' ')]) {
+        match (start, c.is_alphanumeric()) {
+            (None, true) => start = Some(i),
+            (Some(s), false) => {
+                words.push((s..i, squash(&name[s..i])));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    words
+}
+
+/// Terms that may not make up a word, or a run of words, in a player name.
+/// Matching whole words keeps innocent names like "Scunthorpe" usable.
+#[derive(Debug, Default)]
+pub struct Blocklist {
+    terms: Vec<String>,
+}
+
+impl Blocklist {
+    /// One term per line, blank lines and lines starting with # are skipped.
+    pub fn parse(text: &str) -> Self {
+        let terms = text
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(squash)
+            .filter(|term| !term.is_empty())
+            .collect();
+        Self { terms }
+    }
+
+    /// Reads the blocklist file, a missing file means an empty list.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        match fs::read_to_string(path) {
+            Ok(text) => Ok(Self::parse(&text)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.terms.len()
+    }
+
+    /// The part of the name that matched a term, if any. Runs of words are joined first,
+    /// so "b a d" and "b.a.d" match "bad" but "bad sword" doesn't match "badword".
+    pub fn blocked_part<'a>(&self, name: &'a str) -> Option<&'a str> {
+        let words = words(name);
+        for (i, (first, _)) in words.iter().enumerate() {
+            let mut run = String::new();
+            for (range, word) in &words[i..] {
+                run.push_str(word);
+                if self.terms.contains(&run) {
+                    return Some(&name[first.start..range.end]);
+                }
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_are_normalised() {
+        assert_eq!(
+            normalize("  Ａｌｉｃｅ \t  Smith "),
+            Ok("Alice Smith".to_string())
+        );
+        assert_eq!(normalize("bo\u{200B}b\n"), Ok("bob".to_string()));
+        assert_eq!(normalize(" \u{FEFF} "), Err(ErrorCode::NameEmpty));
+        assert_eq!(normalize(&"x".repeat(25)), Err(ErrorCode::NameTooLong));
+        assert_eq!(normalize(&"x".repeat(10_000)), Err(ErrorCode::NameTooLong));
+    }
+
+    #[test]
+    fn blocklist_ignores_case_spacing_and_punctuation() {
+        let list = Blocklist::parse("# comment\n\nBadWord\n");
+        assert_eq!(list.len(), 1);
+        assert_eq!(
+            list.blocked_part("xX b.a.d-w o r d Xx"),
+            Some("b.a.d-w o r d")
+        );
+        assert_eq!(list.blocked_part("ＢＡＤＷＯＲＤ"), Some("ＢＡＤＷＯＲＤ"));
+        assert_eq!(list.blocked_part("bad sword"), None);
+    }
+
+    #[test]
+    fn blocklist_only_matches_whole_words() {
+        let list = Blocklist::parse("cunt\nass\n");
+        assert_eq!(list.blocked_part("Scunthorpe United"), None);
+        assert_eq!(list.blocked_part("Class Clown"), None);
+        assert_eq!(list.blocked_part("kick ASS"), Some("ASS"));
+        assert_eq!(list.blocked_part("a-s-s hat"), Some("a-s-s"));
+    }
+}
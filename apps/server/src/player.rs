@@ -1,47 +1,87 @@
 use std::{
     collections::HashMap,
     fmt,
+    net::IpAddr,
     time::{SystemTime, UNIX_EPOCH},
 };
 
-use anyhow::Result;
 use serde::{Deserialize, Serialize};
 use tokio_mpmc::Sender;
 
-use crate::{ConnectionStatus, HeartbeatId, UnixMs, ws_msg::WsMsg};
+use crate::{
+    HeartbeatId, UnixMs,
+    clock::{ClockEstimator, ClockStats},
+    game::GameState,
+    ws_msg::WsMsg,
+};
 
 pub type PlayerId = u32;
+pub type PendingId = u32;
+
+/// DoHeartbeats that go unanswered this long are forgotten.
+pub const HEARTBEAT_EXPIRY_MS: u64 = 10_000;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum ConnectionStatus {
+    Connected,
+    Disconnected,
+}
 
+/// What everyone in the room may see about a player.
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Player {
     pub pid: PlayerId,
     pub name: String,
     pub score: i32,
     pub buzzed: bool,
-    pub token: String,
+    pub status: ConnectionStatus,
+}
+
+/// What the host sees about a join waiting for approval.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PendingPlayer {
+    pub id: PendingId,
+    pub name: String,
+}
+
+/// A new connection waiting for the host to admit or reject it.
+pub struct PendingJoin {
+    pub pending: PendingPlayer,
+    pub sender: Sender<WsMsg>,
+    pub ip: IpAddr,
+}
+
+impl fmt::Debug for PendingJoin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PendingJoin")
+            .field("pending", &self.pending)
+            .field("ip", &self.ip)
+            .field("sender len", &self.sender.len())
+            .finish()
+    }
 }
 
 pub struct PlayerEntry {
     pub player: Player,
+    pub token: String, // reconnect secret, only ever sent to its owner in NewPlayer
     pub sender: Sender<WsMsg>,
-    pub status: ConnectionStatus,
-    latencies: [u32; 5],
-    times_doheartbeat: HashMap<HeartbeatId, TrackedMessageTime>,
+    pub ip: Option<IpAddr>, // address of the latest connection, for IP bans
+    pub buzzed_early: bool, // buzzed while the question was still being read
+    pub locked_until: Option<UnixMs>, // early-buzz lockout, wall clock
+    clock: ClockEstimator,
+    times_doheartbeat: HashMap<HeartbeatId, UnixMs>, // when each outstanding DoHeartbeat was sent
     hbid_counter: u32,
 }
 
-#[derive(Copy, Clone, Debug)]
-pub struct TrackedMessageTime {
-    pub t_sent: UnixMs,
-    pub t_recv: Option<UnixMs>,
-}
-
 impl fmt::Debug for PlayerEntry {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("PlayerEntry")
             .field("player", &self.player)
-            .field("status", &self.status)
-            .field("latencies", &self.latencies)
+            .field("ip", &self.ip)
+            .field("buzzed_early", &self.buzzed_early)
+            .field("locked_until", &self.locked_until)
+            .field("clock", &self.clock)
             .field("sender len", &self.sender.len())
             .field("times_doheartbeat", &self.times_doheartbeat)
             .field("hbid_counter", &self.hbid_counter)
@@ -50,23 +90,52 @@ impl fmt::Debug for PlayerEntry {
 }
 
 impl PlayerEntry {
-    pub fn new(player: Player, sender: Sender<WsMsg>) -> Self {
+    pub fn new(player: Player, token: String, sender: Sender<WsMsg>) -> Self {
         Self {
             player,
+            token,
             sender,
-            latencies: [0; 5],
+            ip: None,
+            clock: ClockEstimator::default(),
             times_doheartbeat: HashMap::new(),
-            status: ConnectionStatus::Connected,
+            buzzed_early: false,
+            locked_until: None,
             hbid_counter: 0,
         }
     }
 }
 
 impl PlayerEntry {
-    pub fn latency(&self) -> Result<u32> {
-        let sum: u32 = self.latencies.iter().sum();
-        let latencies_len: u32 = self.latencies.len().try_into()?;
-        Ok(sum / latencies_len)
+    /// Estimated one-way latency in milliseconds.
+    pub fn latency(&self) -> u32 {
+        self.clock.one_way_latency()
+    }
+
+    /// When this player pressed a buzz that arrived at `t_recv`, see `ClockEstimator::press_time`.
+    pub fn press_time(&self, t_pressed: Option<UnixMs>, t_recv: UnixMs) -> UnixMs {
+        self.clock.press_time(t_pressed, t_recv)
+    }
+
+    pub fn clock_stats(&self) -> ClockStats {
+        self.clock.stats()
+    }
+
+    pub fn is_locked_out(&self, now: UnixMs) -> bool {
+        self.locked_until.is_some_and(|until| now < until)
+    }
+
+    pub fn state_msg(&self, game_state: &GameState) -> WsMsg {
+        let locked_out = self.is_locked_out(Self::time_ms());
+        WsMsg::PlayerState {
+            pid: self.player.pid,
+            buzzed: self.player.buzzed,
+            score: self.player.score,
+            can_buzz: *game_state == GameState::WaitingForBuzz
+                && !self.player.buzzed
+                && !locked_out,
+            locked_out,
+            locked_until: self.locked_until.filter(|_| locked_out),
+        }
     }
 
     pub fn time_ms() -> u64 {
@@ -78,42 +147,25 @@ impl PlayerEntry {
             .expect("system time in ms exceeds 64-bit integer limit")
     }
 
-    pub fn on_know_dohb_recv(&mut self, hbid: HeartbeatId, t_dohb_recv: UnixMs) -> bool {
-        if let Some(tmt) = self.times_doheartbeat.get_mut(&hbid) {
-            tmt.t_recv = Some(t_dohb_recv);
-            true
-        } else {
-            false
-        }
-    }
-
     pub fn record_dohb(&mut self, hbid: HeartbeatId, t_sent: UnixMs) {
-        self.times_doheartbeat.insert(
-            hbid,
-            TrackedMessageTime {
-                t_sent,
-                t_recv: None,
-            },
-        );
+        self.times_doheartbeat.insert(hbid, t_sent);
     }
 
-    pub fn on_latencyhb(&mut self, hbid: HeartbeatId, t_lathb: u32) -> bool {
-        if let Some(dohb) = self.times_doheartbeat.get(&hbid) {
-            if let Some(lat_fwd) = dohb.delta_32bit() {
-                println!("t_lathb={t_lathb},lat_fwd={lat_fwd}");
-                let lat = t_lathb.saturating_sub(lat_fwd);
-                for i in 1..(self.latencies.len() - 1) {
-                    self.latencies[i - 1] = self.latencies[i];
-                }
-                self.latencies[self.latencies.len() - 1] = lat;
-                self.times_doheartbeat.clear();
-                true
-            } else {
-                println!("WARN (PRE-WARN): DoHeartbeat had time sent but not received");
-                false
-            }
-        } else {
-            false
+    /// Feeds a Heartbeat reply into the clock estimator. `t_dohb_recv` and `t_hb_sent` are
+    /// on the client's clock, `t_recv` on ours. Returns false for unknown/expired heartbeats
+    /// and outlier samples.
+    pub fn on_heartbeat(
+        &mut self,
+        hbid: HeartbeatId,
+        t_dohb_recv: UnixMs,
+        t_hb_sent: UnixMs,
+        t_recv: UnixMs,
+    ) -> bool {
+        match self.times_doheartbeat.remove(&hbid) {
+            Some(t_sent) => self
+                .clock
+                .add_sample(t_sent, t_dohb_recv, t_hb_sent, t_recv),
+            None => false,
         }
     }
 
@@ -121,41 +173,36 @@ impl PlayerEntry {
         let t_part: u32 = (t_sent % 1_000)
             .try_into()
             .expect("ms part of time exceeds 32-bit integer limit (impossible)");
-        t_part + (self.hbid_counter * 1_000)
+        let hbid = t_part.wrapping_add(self.hbid_counter.wrapping_mul(1_000));
+        self.hbid_counter = self.hbid_counter.wrapping_add(1);
+        hbid
+    }
+
+    /// Drops heartbeats the client never finished answering, returns how many.
+    pub fn expire_heartbeats(&mut self, now: UnixMs) -> usize {
+        let before = self.times_doheartbeat.len();
+        self.times_doheartbeat
+            .retain(|_, t_sent| now.saturating_sub(*t_sent) < HEARTBEAT_EXPIRY_MS);
+        before - self.times_doheartbeat.len()
     }
 
-    pub async fn heartbeat(&mut self) -> anyhow::Result<()> {
+    /// Records a new DoHeartbeat for this player, the caller sends it once the room lock is released.
+    pub fn start_heartbeat(&mut self) -> WsMsg {
         let t_sent = Self::time_ms();
         let hbid = self.generate_hbid(t_sent);
-        self.sender
-            .send(WsMsg::DoHeartbeat { hbid, t_sent })
-            .await?;
         self.record_dohb(hbid, t_sent);
-        Ok(())
-    }
-}
-
-impl TrackedMessageTime {
-    pub fn delta(&self) -> Option<u64> {
-        self.t_recv.map(|x| x.saturating_sub(self.t_sent))
-    }
-
-    pub fn delta_32bit(&self) -> Option<u32> {
-        self.delta().map(|x| {
-            x.try_into()
-                .expect("delta_32bit used when delta exceeds 32-bit integer limit")
-        })
+        WsMsg::DoHeartbeat { hbid, t_sent }
     }
 }
 
 impl Player {
-    pub fn new(pid: PlayerId, name: String, score: i32, buzzed: bool, token: String) -> Self {
+    pub fn new(pid: PlayerId, name: String) -> Self {
         Self {
             pid,
             name,
-            score,
-            buzzed,
-            token,
+            score: 0,
+            buzzed: false,
+            status: ConnectionStatus::Connected,
         }
     }
 }
@@ -0,0 +1,256 @@
+use std::{
+    collections::HashMap,
+    mem::{Discriminant, discriminant},
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::Instant,
+};
+
+use serde::Serialize;
+
+use crate::ws_msg::{Origin, WsMsg};
+
+/// Frames longer than this are dropped without being parsed.
+pub const MAX_FRAME_BYTES: usize = 4 * 1024;
+/// Hard cap for the WebSocket layer, a bigger message closes the socket outright.
+pub const MAX_SOCKET_MESSAGE_BYTES: usize = 64 * 1024;
+
+/// Any frame at all, checked before parsing so garbage is limited too.
+const FRAMES: Limit = Limit::new(30, 15.0);
+/// Dropped frames a connection may rack up before it is disconnected.
+const STRIKES: Limit = Limit::new(20, 0.5);
+
+/// Burst size and sustained rate of a token bucket.
+#[derive(Clone, Copy, Debug)]
+pub struct Limit {
+    pub burst: u32,
+    pub per_second: f64,
+}
+
+impl Limit {
+    pub const fn new(burst: u32, per_second: f64) -> Self {
+        Self { burst, per_second }
+    }
+
+    /// The limit for one message type, each type gets its own bucket.
+    pub fn of(msg: &WsMsg) -> Self {
+        match (msg.origin(), msg) {
+            (_, WsMsg::Buzz { .. }) => Limit::new(3, 2.0),
+            (_, WsMsg::Heartbeat { .. } | WsMsg::LatencyOfHeartbeat { .. }) => Limit::new(10, 4.0),
+            (Origin::Player, _) => Limit::new(5, 1.0),
+            (Origin::Host, _) => Limit::new(20, 10.0),
+            (Origin::Server, _) => Limit::new(5, 0.5), // refused anyway
+        }
+    }
+}
+
+#[derive(Debug)]
+struct TokenBucket {
+    limit: Limit,
+    tokens: f64,
+    last: Instant,
+}
+
+impl TokenBucket {
+    fn new(limit: Limit, now: Instant) -> Self {
+        Self {
+            limit,
+            tokens: limit.burst.into(),
+            last: now,
+        }
+    }
+
+    fn try_take(&mut self, now: Instant) -> bool {
+        let elapsed = now.saturating_duration_since(self.last).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.limit.per_second).min(self.limit.burst.into());
+        self.last = now;
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// What to do with a frame from a client.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Verdict {
+    Allow,
+    Drop,
+    Disconnect,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Hit {
+    RateLimited,
+    Oversize,
+    Invalid,
+}
+
+/// Limit hits for a room, shared with its connections so counting never takes the room lock.
+#[derive(Debug, Default)]
+pub struct LimitCounters {
+    rate_limited: AtomicU64,
+    oversize: AtomicU64,
+    invalid: AtomicU64,
+    disconnected: AtomicU64,
+}
+
+/// A point-in-time copy of `LimitCounters`, as shown to the host.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LimitStats {
+    pub rate_limited: u64, // frames dropped by a token bucket
+    pub oversize: u64,     // frames over MAX_FRAME_BYTES
+    pub invalid: u64,      // frames that weren't a WsMsg
+    pub disconnected: u64, // connections closed for running out of strikes
+}
+
+impl LimitCounters {
+    pub fn stats(&self) -> LimitStats {
+        LimitStats {
+            rate_limited: self.rate_limited.load(Ordering::Relaxed),
+            oversize: self.oversize.load(Ordering::Relaxed),
+            invalid: self.invalid.load(Ordering::Relaxed),
+            disconnected: self.disconnected.load(Ordering::Relaxed),
+        }
+    }
+
+    fn count(&self, hit: Hit) {
+        let counter = match hit {
+            Hit::RateLimited => &self.rate_limited,
+            Hit::Oversize => &self.oversize,
+            Hit::Invalid => &self.invalid,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Flood protection for one WebSocket connection.
+#[derive(Debug)]
+pub struct ConnectionLimiter {
+    frames: TokenBucket,
+    per_type: HashMap<Discriminant<WsMsg>, TokenBucket>,
+    strikes: TokenBucket,
+    counters: Arc<LimitCounters>,
+}
+
+impl ConnectionLimiter {
+    pub fn new(counters: Arc<LimitCounters>) -> Self {
+        let now = Instant::now();
+        Self {
+            frames: TokenBucket::new(FRAMES, now),
+            per_type: HashMap::new(),
+            strikes: TokenBucket::new(STRIKES, now),
+            counters,
+        }
+    }
+
+    /// Checks a raw frame before it is parsed.
+    pub fn check_frame(&mut self, len: usize) -> Verdict {
+        let now = Instant::now();
+        if len > MAX_FRAME_BYTES {
+            self.strike(Hit::Oversize, now)
+        } else if !self.frames.try_take(now) {
+            self.strike(Hit::RateLimited, now)
+        } else {
+            Verdict::Allow
+        }
+    }
+
+    /// Checks a parsed message against the bucket for its type.
+    pub fn check_msg(&mut self, msg: &WsMsg) -> Verdict {
+        let now = Instant::now();
+        let bucket = self
+            .per_type
+            .entry(discriminant(msg))
+            .or_insert_with(|| TokenBucket::new(Limit::of(msg), now));
+        if bucket.try_take(now) {
+            Verdict::Allow
+        } else {
+            self.strike(Hit::RateLimited, now)
+        }
+    }
+
+    /// Records a frame that didn't parse as a message.
+    pub fn invalid(&mut self) -> Verdict {
+        self.strike(Hit::Invalid, Instant::now())
+    }
+
+    fn strike(&mut self, hit: Hit, now: Instant) -> Verdict {
+        self.counters.count(hit);
+        if self.strikes.try_take(now) {
+            Verdict::Drop
+        } else {
+            self.counters.disconnected.fetch_add(1, Ordering::Relaxed);
+            Verdict::Disconnect
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn buckets_refill_over_time() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new(Limit::new(2, 1.0), start);
+        assert!(bucket.try_take(start));
+        assert!(bucket.try_take(start));
+        assert!(!bucket.try_take(start));
+        assert!(!bucket.try_take(start + Duration::from_millis(500)));
+        assert!(bucket.try_take(start + Duration::from_millis(1000)));
+        // Never more than the burst, however long it sat idle
+        let later = start + Duration::from_secs(60);
+        assert!(bucket.try_take(later));
+        assert!(bucket.try_take(later));
+        assert!(!bucket.try_take(later));
+    }
+
+    #[test]
+    fn each_message_type_has_its_own_bucket() {
+        let counters = Arc::new(LimitCounters::default());
+        let mut limiter = ConnectionLimiter::new(counters.clone());
+        let burst = Limit::of(&WsMsg::Buzz { t_pressed: None }).burst;
+        for _ in 0..burst {
+            assert_eq!(
+                limiter.check_msg(&WsMsg::Buzz { t_pressed: None }),
+                Verdict::Allow
+            );
+        }
+        assert_eq!(
+            limiter.check_msg(&WsMsg::Buzz { t_pressed: None }),
+            Verdict::Drop
+        );
+        let wager = WsMsg::Wager { amount: 100 };
+        assert_eq!(limiter.check_msg(&wager), Verdict::Allow);
+        assert_eq!(counters.stats().rate_limited, 1);
+    }
+
+    #[test]
+    fn running_out_of_strikes_disconnects() {
+        let counters = Arc::new(LimitCounters::default());
+        let mut limiter = ConnectionLimiter::new(counters.clone());
+        assert_eq!(limiter.check_frame(MAX_FRAME_BYTES), Verdict::Allow);
+        assert_eq!(limiter.check_frame(MAX_FRAME_BYTES + 1), Verdict::Drop);
+        for _ in 1..STRIKES.burst {
+            assert_eq!(limiter.invalid(), Verdict::Drop);
+        }
+        assert_eq!(limiter.invalid(), Verdict::Disconnect);
+        assert_eq!(
+            counters.stats(),
+            LimitStats {
+                rate_limited: 0,
+                oversize: 1,
+                invalid: STRIKES.burst.into(),
+                disconnected: 1,
+            }
+        );
+    }
+}
@@ -1,9 +1,12 @@
+use std::fmt;
+
 use serde::{Deserialize, Serialize};
 
 use crate::{
     HeartbeatId, UnixMs,
-    game::{Category, GameState},
-    player::{Player, PlayerId},
+    clock::ClockStats,
+    game::{Category, FinalQuestion, GameState, RankedBuzz, Reveal, ScoreAdjustment, TimerKind},
+    player::{PendingId, PendingPlayer, Player, PlayerId},
 };
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -20,12 +23,18 @@ pub enum WsMsg {
     // Game State Broadcast
     GameState {
         state: GameState,
+        round: usize,
         categories: Vec<Category>,
         players: Vec<Player>,
         #[serde(rename = "currentQuestion")]
         current_question: Option<(usize, usize)>,
         #[serde(rename = "currentBuzzer")]
         current_buzzer: Option<PlayerId>,
+        #[serde(rename = "finalQuestion")]
+        final_question: Option<FinalQuestion>,
+        reveal: Option<Reveal>,
+        #[serde(rename = "boardControl")]
+        board_control: Option<PlayerId>, // who picks the next clue
     },
 
     PlayerState {
@@ -34,6 +43,10 @@ pub enum WsMsg {
         score: i32,
         #[serde(rename = "canBuzz")]
         can_buzz: bool,
+        #[serde(rename = "lockedOut")]
+        locked_out: bool,
+        #[serde(rename = "lockedUntil")]
+        locked_until: Option<UnixMs>,
     },
 
     // Host Actions
@@ -53,17 +66,74 @@ pub enum WsMsg {
         correct: bool,
     },
 
+    // Daily Double
+    DailyDouble {
+        pid: PlayerId,
+        #[serde(rename = "minWager")]
+        min_wager: u32,
+        #[serde(rename = "maxWager")]
+        max_wager: u32,
+    },
+    Wager {
+        amount: u32,
+    },
+    WagerAccepted {
+        pid: PlayerId,
+        amount: u32,
+    },
+    WagerRejected {
+        #[serde(rename = "minWager")]
+        min_wager: u32,
+        #[serde(rename = "maxWager")]
+        max_wager: u32,
+    },
+
+    // Final Jeopardy
+    FinalWager {
+        amount: u32,
+    },
+    FinalAnswer {
+        answer: String,
+    },
+    FinalSubmitted {
+        pid: PlayerId,
+    },
+    FinalReveal {
+        pid: PlayerId,
+        name: String,
+        answer: String,
+        wager: u32,
+    },
+
+    // Timers
+    TimerTick {
+        kind: TimerKind,
+        #[serde(rename = "secondsLeft")]
+        seconds_left: u32,
+    },
+    AnswerRevealed {
+        answer: String,
+        #[serde(default)]
+        pid: Option<PlayerId>, // who got it right
+    },
+
     // Buzzer
     #[serde(alias = "BuzzEnable")]
     BuzzEnable {},
     #[serde(alias = "BuzzDisable")]
     BuzzDisable {},
     #[serde(alias = "Buzz")]
-    Buzz {},
+    Buzz {
+        #[serde(default, rename = "tPressed")]
+        t_pressed: Option<UnixMs>, // client clock, used by buzz arbitration if sent
+    },
     Buzzed {
         pid: PlayerId,
         name: String,
     },
+    BuzzRanking {
+        ranking: Vec<RankedBuzz>,
+    },
 
     // Heartbeats
     DoHeartbeat {
@@ -73,6 +143,7 @@ pub enum WsMsg {
     Heartbeat {
         hbid: HeartbeatId,
         t_dohb_recv: UnixMs,
+        t_hb_sent: Option<UnixMs>,
     },
     GotHeartbeat {
         hbid: HeartbeatId,
@@ -81,4 +152,507 @@ pub enum WsMsg {
         hbid: HeartbeatId,
         t_lat: UnixMs,
     },
+    PlayerClock {
+        pid: PlayerId,
+        clock: ClockStats,
+    },
+
+    // Moderation
+    KickPlayer {
+        pid: PlayerId,
+        #[serde(default)]
+        reason: String,
+    },
+    BanPlayer {
+        pid: PlayerId,
+        #[serde(default)]
+        reason: String,
+        #[serde(default, rename = "byName")]
+        by_name: bool, // also refuse new joins under the same name
+        #[serde(default, rename = "byIp")]
+        by_ip: bool, // also refuse connections from the same address
+    },
+    Kicked {
+        reason: String,
+        banned: bool,
+    },
+
+    // Join Approval
+    AwaitingApproval {},
+    PendingList(Vec<PendingPlayer>),
+    AdmitPlayer {
+        id: PendingId,
+    },
+    RejectPlayer {
+        id: PendingId,
+        #[serde(default)]
+        reason: String,
+    },
+    JoinRejected {
+        reason: String,
+    },
+
+    // Score Corrections
+    SetScore {
+        pid: PlayerId,
+        score: i32,
+        #[serde(default)]
+        reason: String,
+    },
+    AdjustScore {
+        pid: PlayerId,
+        delta: i32,
+        #[serde(default)]
+        reason: String,
+    },
+    GetScoreLog {},
+    ScoreLog {
+        entries: Vec<ScoreAdjustment>,
+    },
+
+    // Board Control
+    PlayerChoice {
+        #[serde(rename = "categoryIndex")]
+        category_index: usize,
+        #[serde(rename = "questionIndex")]
+        question_index: usize,
+    },
+    SetControl {
+        pid: PlayerId,
+    },
+
+    // Rulings
+    VoidClue {
+        #[serde(default, rename = "returnToBoard")]
+        return_to_board: bool, // false marks it answered
+    },
+    Undo {}, // reverts the last HostChecked, VoidClue or answer timeout
+
+    // Errors
+    Error {
+        code: ErrorCode,
+        message: String,
+    },
+}
+
+/// Why a message from a client was refused.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum ErrorCode {
+    NotHost,         // host command from a player or display
+    NotPlayer,       // player command from the host or a display
+    ServerOnly,      // message only the server sends
+    SessionReplaced, // a newer connection took over this seat
+    RoomClosed,      // the host deleted the room
+    RoomExpired,     // nobody used the room for a long time
+    NameEmpty,
+    NameTooLong,
+    NameBlocked, // matched the server's name blocklist
+    NameTaken,   // another player in the room already uses it
+    RateLimited, // sent too many messages, the connection is closed
+    NothingToUndo,
+    NotInControl,   // PlayerChoice from a player who doesn't have board control
+    InvalidChoice,  // the clue isn't on the board, or the board isn't showing
+    NothingToJudge, // HostChecked while nobody is answering
+    WrongState,     // HostReady while there is nothing to move on from
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ErrorCode::NotHost => write!(f, "only the host can send this message"),
+            ErrorCode::NotPlayer => write!(f, "only players can send this message"),
+            ErrorCode::ServerOnly => write!(f, "this message is only sent by the server"),
+            ErrorCode::SessionReplaced => write!(f, "signed in again from another connection"),
+            ErrorCode::RoomClosed => write!(f, "the room was closed by the host"),
+            ErrorCode::RoomExpired => write!(f, "the room was closed after sitting idle"),
+            ErrorCode::NameEmpty => write!(f, "please enter a name"),
+            ErrorCode::NameTooLong => write!(
+                f,
+                "names can be at most {} characters",
+                crate::names::MAX_NAME_CHARS
+            ),
+            ErrorCode::NameBlocked => write!(f, "that name isn't allowed"),
+            ErrorCode::NameTaken => write!(f, "someone in this room already has that name"),
+            ErrorCode::RateLimited => write!(f, "too many messages, slow down"),
+            ErrorCode::NothingToUndo => write!(f, "there is no ruling to undo"),
+            ErrorCode::NotInControl => write!(f, "it isn't your turn to pick a clue"),
+            ErrorCode::InvalidChoice => write!(f, "that clue can't be picked right now"),
+            ErrorCode::NothingToJudge => write!(f, "nobody is answering right now"),
+            ErrorCode::WrongState => write!(f, "there is nothing to move on from right now"),
+        }
+    }
+}
+
+/// Which kind of connection may send a message to the server.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Origin {
+    Host,
+    Player,
+    Server, // never accepted from a client
+}
+
+impl WsMsg {
+    pub fn origin(&self) -> Origin {
+        match self {
+            WsMsg::StartGame {}
+            | WsMsg::EndGame {}
+            | WsMsg::HostChoice { .. }
+            | WsMsg::HostReady {}
+            | WsMsg::HostChecked { .. }
+            | WsMsg::KickPlayer { .. }
+            | WsMsg::BanPlayer { .. }
+            | WsMsg::AdmitPlayer { .. }
+            | WsMsg::RejectPlayer { .. }
+            | WsMsg::SetScore { .. }
+            | WsMsg::AdjustScore { .. }
+            | WsMsg::GetScoreLog {}
+            | WsMsg::VoidClue { .. }
+            | WsMsg::SetControl { .. }
+            | WsMsg::Undo {} => Origin::Host,
+
+            WsMsg::Wager { .. }
+            | WsMsg::FinalWager { .. }
+            | WsMsg::FinalAnswer { .. }
+            | WsMsg::Buzz { .. }
+            | WsMsg::PlayerChoice { .. }
+            | WsMsg::Heartbeat { .. }
+            | WsMsg::LatencyOfHeartbeat { .. } => Origin::Player,
+
+            WsMsg::Witness { .. }
+            | WsMsg::PlayerList(_)
+            | WsMsg::NewPlayer { .. }
+            | WsMsg::GameState { .. }
+            | WsMsg::PlayerState { .. }
+            | WsMsg::DailyDouble { .. }
+            | WsMsg::WagerAccepted { .. }
+            | WsMsg::WagerRejected { .. }
+            | WsMsg::FinalSubmitted { .. }
+            | WsMsg::FinalReveal { .. }
+            | WsMsg::TimerTick { .. }
+            | WsMsg::AnswerRevealed { .. }
+            | WsMsg::BuzzEnable {}
+            | WsMsg::BuzzDisable {}
+            | WsMsg::Buzzed { .. }
+            | WsMsg::BuzzRanking { .. }
+            | WsMsg::DoHeartbeat { .. }
+            | WsMsg::GotHeartbeat { .. }
+            | WsMsg::PlayerClock { .. }
+            | WsMsg::Kicked { .. }
+            | WsMsg::AwaitingApproval {}
+            | WsMsg::PendingList(_)
+            | WsMsg::JoinRejected { .. }
+            | WsMsg::ScoreLog { .. }
+            | WsMsg::Error { .. } => Origin::Server,
+        }
+    }
+
+    pub fn error(code: ErrorCode) -> Self {
+        WsMsg::Error {
+            code,
+            message: code.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::{collections::HashSet, mem::discriminant};
+
+    use super::*;
+    use crate::game::Question;
+
+    /// One message of every variant, with the origin it must have.
+    pub(crate) fn samples() -> Vec<(WsMsg, Origin)> {
+        let player = Player::new(1, "alice".to_string());
+        let category = Category {
+            title: "Rivers".to_string(),
+            questions: vec![Question {
+                text: "Longest river in Africa".to_string(),
+                answer: "The Nile".to_string(),
+                value: 200,
+                answered: false,
+                answer_seconds: None,
+                daily_double: false,
+            }],
+        };
+        let final_question = FinalQuestion {
+            category: "Capitals".to_string(),
+            text: "Capital of Australia".to_string(),
+            answer: "Canberra".to_string(),
+        };
+        vec![
+            (
+                WsMsg::Witness {
+                    msg: Box::new(WsMsg::Buzz { t_pressed: None }),
+                },
+                Origin::Server,
+            ),
+            (WsMsg::PlayerList(vec![player.clone()]), Origin::Server),
+            (
+                WsMsg::NewPlayer {
+                    pid: 1,
+                    token: "tok".to_string(),
+                },
+                Origin::Server,
+            ),
+            (
+                WsMsg::GameState {
+                    state: GameState::Selection,
+                    round: 0,
+                    categories: vec![category],
+                    players: vec![player],
+                    current_question: Some((0, 0)),
+                    current_buzzer: Some(1),
+                    final_question: Some(final_question),
+                    reveal: Some(Reveal {
+                        answer: "The Nile".to_string(),
+                        pid: Some(1),
+                        name: Some("alice".to_string()),
+                    }),
+                    board_control: Some(1),
+                },
+                Origin::Server,
+            ),
+            (
+                WsMsg::PlayerState {
+                    pid: 1,
+                    buzzed: false,
+                    score: 200,
+                    can_buzz: true,
+                    locked_out: false,
+                    locked_until: None,
+                },
+                Origin::Server,
+            ),
+            (WsMsg::StartGame {}, Origin::Host),
+            (WsMsg::EndGame {}, Origin::Host),
+            (
+                WsMsg::HostChoice {
+                    category_index: 0,
+                    question_index: 0,
+                },
+                Origin::Host,
+            ),
+            (WsMsg::HostReady {}, Origin::Host),
+            (WsMsg::HostChecked { correct: true }, Origin::Host),
+            (
+                WsMsg::DailyDouble {
+                    pid: 1,
+                    min_wager: 5,
+                    max_wager: 1000,
+                },
+                Origin::Server,
+            ),
+            (WsMsg::Wager { amount: 500 }, Origin::Player),
+            (
+                WsMsg::WagerAccepted {
+                    pid: 1,
+                    amount: 500,
+                },
+                Origin::Server,
+            ),
+            (
+                WsMsg::WagerRejected {
+                    min_wager: 5,
+                    max_wager: 1000,
+                },
+                Origin::Server,
+            ),
+            (WsMsg::FinalWager { amount: 100 }, Origin::Player),
+            (
+                WsMsg::FinalAnswer {
+                    answer: "What is Canberra?".to_string(),
+                },
+                Origin::Player,
+            ),
+            (WsMsg::FinalSubmitted { pid: 1 }, Origin::Server),
+            (
+                WsMsg::FinalReveal {
+                    pid: 1,
+                    name: "alice".to_string(),
+                    answer: "What is Canberra?".to_string(),
+                    wager: 100,
+                },
+                Origin::Server,
+            ),
+            (
+                WsMsg::TimerTick {
+                    kind: TimerKind::Answer,
+                    seconds_left: 3,
+                },
+                Origin::Server,
+            ),
+            (
+                WsMsg::AnswerRevealed {
+                    answer: "The Nile".to_string(),
+                    pid: Some(1),
+                },
+                Origin::Server,
+            ),
+            (WsMsg::BuzzEnable {}, Origin::Server),
+            (WsMsg::BuzzDisable {}, Origin::Server),
+            (WsMsg::Buzz { t_pressed: None }, Origin::Player),
+            (
+                WsMsg::Buzzed {
+                    pid: 1,
+                    name: "alice".to_string(),
+                },
+                Origin::Server,
+            ),
+            (
+                WsMsg::BuzzRanking {
+                    ranking: vec![RankedBuzz {
+                        pid: 1,
+                        name: "alice".to_string(),
+                        received_at: 1_000,
+                        latency: 20,
+                        adjusted_at: 980,
+                    }],
+                },
+                Origin::Server,
+            ),
+            (
+                WsMsg::DoHeartbeat {
+                    hbid: 7,
+                    t_sent: 1_000,
+                },
+                Origin::Server,
+            ),
+            (
+                WsMsg::Heartbeat {
+                    hbid: 7,
+                    t_dohb_recv: 1_010,
+                    t_hb_sent: Some(1_011),
+                },
+                Origin::Player,
+            ),
+            (WsMsg::GotHeartbeat { hbid: 7 }, Origin::Server),
+            (
+                WsMsg::LatencyOfHeartbeat { hbid: 7, t_lat: 20 },
+                Origin::Player,
+            ),
+            (
+                WsMsg::PlayerClock {
+                    pid: 1,
+                    clock: ClockStats::default(),
+                },
+                Origin::Server,
+            ),
+            (WsMsg::error(ErrorCode::NotHost), Origin::Server),
+            (
+                WsMsg::KickPlayer {
+                    pid: 1,
+                    reason: "spamming the buzzer".to_string(),
+                },
+                Origin::Host,
+            ),
+            (
+                WsMsg::BanPlayer {
+                    pid: 1,
+                    reason: "offensive name".to_string(),
+                    by_name: true,
+                    by_ip: false,
+                },
+                Origin::Host,
+            ),
+            (
+                WsMsg::Kicked {
+                    reason: "offensive name".to_string(),
+                    banned: true,
+                },
+                Origin::Server,
+            ),
+            (WsMsg::AwaitingApproval {}, Origin::Server),
+            (
+                WsMsg::PendingList(vec![PendingPlayer {
+                    id: 0,
+                    name: "bob".to_string(),
+                }]),
+                Origin::Server,
+            ),
+            (WsMsg::AdmitPlayer { id: 0 }, Origin::Host),
+            (
+                WsMsg::RejectPlayer {
+                    id: 0,
+                    reason: "game already started".to_string(),
+                },
+                Origin::Host,
+            ),
+            (
+                WsMsg::JoinRejected {
+                    reason: "game already started".to_string(),
+                },
+                Origin::Server,
+            ),
+            (
+                WsMsg::SetScore {
+                    pid: 1,
+                    score: 400,
+                    reason: "misheard answer".to_string(),
+                },
+                Origin::Host,
+            ),
+            (
+                WsMsg::AdjustScore {
+                    pid: 1,
+                    delta: -200,
+                    reason: String::new(),
+                },
+                Origin::Host,
+            ),
+            (WsMsg::GetScoreLog {}, Origin::Host),
+            (
+                WsMsg::ScoreLog {
+                    entries: vec![ScoreAdjustment {
+                        pid: 1,
+                        name: "alice".to_string(),
+                        from: 0,
+                        to: 400,
+                        reason: "misheard answer".to_string(),
+                        at: 1_000,
+                    }],
+                },
+                Origin::Server,
+            ),
+            (WsMsg::Undo {}, Origin::Host),
+            (
+                WsMsg::VoidClue {
+                    return_to_board: true,
+                },
+                Origin::Host,
+            ),
+            (
+                WsMsg::PlayerChoice {
+                    category_index: 0,
+                    question_index: 0,
+                },
+                Origin::Player,
+            ),
+            (WsMsg::SetControl { pid: 1 }, Origin::Host),
+        ]
+    }
+
+    #[test]
+    fn samples_are_distinct_variants() {
+        let seen: HashSet<_> = samples().iter().map(|(msg, _)| discriminant(msg)).collect();
+        assert_eq!(seen.len(), samples().len(), "duplicate sample variant");
+    }
+
+    #[test]
+    fn every_variant_round_trips() {
+        for (msg, _) in samples() {
+            let json = serde_json::to_string(&msg).expect("serialize");
+            let back: WsMsg = serde_json::from_str(&json).expect("deserialize");
+            assert_eq!(discriminant(&back), discriminant(&msg), "{json}");
+            assert_eq!(serde_json::to_string(&back).expect("serialize"), json);
+        }
+    }
+
+    #[test]
+    fn every_variant_has_the_expected_origin() {
+        for (msg, expected) in samples() {
+            assert_eq!(msg.origin(), expected, "{msg:?}");
+        }
+    }
 }
@@ -29,7 +29,6 @@ export function useWebSocket({
   const [isConnected, setIsConnected] = useState(false);
   const wsRef = useRef<WebSocket | null>(null);
   const reconnectTimeoutRef = useRef<number | null>(null);
-  const lastHeartbeatRef = useRef<number>(0);
   const onMessageRef = useRef(onMessage);
 
   // Keep onMessage ref updated
@@ -76,15 +75,9 @@ export function useWebSocket({
         const message = JSON.parse(event.data) as NetworkMessage;
         const [type, payload] = Object.entries(message)[0];
         if (type === "DoHeartbeat") {
-          const t_dohb_recv = Date.now();
-          ws.send(JSON.stringify({ Heartbeat: { t_dohb_recv } }));
-          lastHeartbeatRef.current = t_dohb_recv;
-          return;
-        }
-        if (type === "GotHeartbeat") {
           const hbid = (payload as any).hbid;
-          const t_lat = Date.now() - lastHeartbeatRef.current;
-          ws.send(JSON.stringify({ LatencyOfHeartbeat: { hbid, t_lat } }));
+          const t_dohb_recv = Date.now();
+          ws.send(JSON.stringify({ Heartbeat: { hbid, t_dohb_recv, t_hb_sent: Date.now() } }));
           return;
         }
         onMessageRef.current(message);